@@ -23,6 +23,12 @@ use sgx_urts::SgxEnclave;
 extern crate libc;
 mod io_uring_ocall;
 pub use io_uring_ocall::*;
+mod park_ocall;
+pub use park_ocall::*;
+mod sched_ocall;
+pub use sched_ocall::*;
+mod time_ocall;
+pub use time_ocall::*;
 
 static ENCLAVE_FILE: &'static str = "enclave.signed.so";
 
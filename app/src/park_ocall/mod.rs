@@ -0,0 +1,55 @@
+use sgx_types::{c_int, c_long, c_void};
+use libc::{eventfd, ppoll, pollfd, read, timespec, write, EFD_CLOEXEC, EFD_NONBLOCK, POLLIN};
+use std::{mem, ptr};
+
+#[no_mangle]
+pub extern "C" fn ocall_eventfd_create() -> c_int {
+    unsafe { eventfd(0, EFD_CLOEXEC | EFD_NONBLOCK) }
+}
+
+/// Block the calling thread until either eventfd is signalled or the timeout
+/// (in nanoseconds, or negative for no timeout) expires, and then reset the
+/// signalled eventfds. The completion eventfd is optional (negative for none).
+#[no_mangle]
+pub extern "C" fn ocall_eventfd_wait(fd: c_int, completion_fd: c_int, timeout_ns: c_long) -> c_int {
+    // A negative fd is ignored by ppoll
+    let mut poll_fds = [
+        pollfd {
+            fd,
+            events: POLLIN,
+            revents: 0,
+        },
+        pollfd {
+            fd: completion_fd,
+            events: POLLIN,
+            revents: 0,
+        },
+    ];
+    let timeout = timespec {
+        tv_sec: timeout_ns / 1_000_000_000,
+        tv_nsec: timeout_ns % 1_000_000_000,
+    };
+    let timeout_ptr = if timeout_ns < 0 {
+        ptr::null()
+    } else {
+        &timeout as *const timespec
+    };
+
+    unsafe {
+        let ret = ppoll(poll_fds.as_mut_ptr(), 2, timeout_ptr, ptr::null());
+        if ret > 0 {
+            for poll_fd in poll_fds.iter().filter(|fd| fd.revents & POLLIN != 0) {
+                let mut value: u64 = 0;
+                let buf = &mut value as *mut u64 as *mut c_void;
+                read(poll_fd.fd, buf, mem::size_of::<u64>());
+            }
+        }
+        ret
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_eventfd_signal(fd: c_int) -> c_int {
+    let value: u64 = 1;
+    unsafe { write(fd, &value as *const u64 as *const c_void, mem::size_of::<u64>()) as c_int }
+}
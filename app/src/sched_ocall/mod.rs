@@ -0,0 +1,18 @@
+use sgx_types::c_int;
+use libc::{cpu_set_t, sched_setaffinity, CPU_SET, CPU_ZERO};
+use std::mem;
+
+#[no_mangle]
+pub extern "C" fn ocall_sched_setaffinity(cpu: c_int) -> c_int {
+    if cpu < 0 {
+        return -1;
+    }
+    unsafe {
+        let mut cpu_set: cpu_set_t = mem::zeroed();
+        CPU_ZERO(&mut cpu_set);
+        CPU_SET(cpu as usize, &mut cpu_set);
+        // A pid of zero means the calling thread, which is the one that makes
+        // the OCall
+        sched_setaffinity(0, mem::size_of::<cpu_set_t>(), &cpu_set)
+    }
+}
@@ -0,0 +1,12 @@
+use libc::{clock_gettime, timespec, CLOCK_MONOTONIC};
+
+/// Returns the time of the monotonic clock of the host in nanoseconds.
+#[no_mangle]
+pub extern "C" fn ocall_clock_monotonic_ns() -> u64 {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { clock_gettime(CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
@@ -0,0 +1,184 @@
+use alloc::collections::BTreeMap;
+
+use crate::prelude::*;
+
+/// An actor, which is a function run repeatedly by worker threads to do some
+/// background work, e.g., reaping the completions of an io_uring.
+///
+/// The function returns whether it has done any work. The worker threads only
+/// park when no actors have work to do. While parked, the threads wake up
+/// periodically to run the actors. To be more responsive, an event source that
+/// an actor polls may call `Handle::unpark_all`.
+///
+/// An actor is never run by two threads at the same time. By default, it is
+/// run by every worker thread in every round of its loop.
+pub struct Actor {
+    func: Box<dyn Fn() -> bool + Send + 'static>,
+    thread_id: Option<u32>,
+    interval: u32,
+}
+
+impl Actor {
+    pub fn new(func: impl Fn() -> bool + Send + 'static) -> Self {
+        Self {
+            func: Box::new(func),
+            thread_id: None,
+            interval: 1,
+        }
+    }
+
+    /// Bind the actor to the worker thread of the given index.
+    pub fn thread_id(mut self, thread_id: u32) -> Self {
+        self.thread_id = Some(thread_id);
+        self
+    }
+
+    /// Run the actor once every `interval` rounds of a worker thread, which
+    /// must be non-zero.
+    pub fn interval(mut self, interval: u32) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl<F: Fn() -> bool + Send + 'static> From<F> for Actor {
+    fn from(func: F) -> Self {
+        Actor::new(func)
+    }
+}
+
+/// The ID of a registered actor.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ActorId(u64);
+
+/// The actors of an executor.
+///
+/// The worker threads do not lock the registry in each round. Instead, each of
+/// them keeps its own list of actors, which is only refreshed when the version
+/// of the registry changes.
+pub(crate) struct ActorRegistry {
+    entries: Mutex<BTreeMap<ActorId, Arc<ActorEntry>>>,
+    version: AtomicU64,
+    next_id: AtomicU64,
+}
+
+struct ActorEntry {
+    func: Mutex<Box<dyn Fn() -> bool + Send + 'static>>,
+    thread_id: Option<u32>,
+    interval: u32,
+    is_removed: AtomicBool,
+}
+
+impl ActorEntry {
+    /// Run the actor unless it is being run by another thread.
+    fn run(&self) -> bool {
+        match self.func.try_lock() {
+            None => false,
+            Some(func) => func(),
+        }
+    }
+}
+
+impl ActorRegistry {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(BTreeMap::new()),
+            version: AtomicU64::new(0),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn register(&self, actor: Actor, parallelism: u32) -> Result<ActorId> {
+        if actor.interval == 0 {
+            return Err("the interval of an actor must be non-zero");
+        }
+        if actor.thread_id.map_or(false, |id| id >= parallelism) {
+            return Err("the thread ID of an actor is out of range");
+        }
+
+        let id = ActorId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let entry = Arc::new(ActorEntry {
+            func: Mutex::new(actor.func),
+            thread_id: actor.thread_id,
+            interval: actor.interval,
+            is_removed: AtomicBool::new(false),
+        });
+        self.entries.lock().insert(id, entry);
+        self.version.fetch_add(1, Ordering::Release);
+        Ok(id)
+    }
+
+    pub fn unregister(&self, id: ActorId) -> Result<()> {
+        let entry = self
+            .entries
+            .lock()
+            .remove(&id)
+            .ok_or("the actor is not found")?;
+        // A worker thread may still hold the entry until it refreshes its list
+        entry.is_removed.store(true, Ordering::Release);
+        self.version.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Run all actors regardless of their threads and intervals. Returns
+    /// whether any of them has done some work.
+    pub fn run_all(&self) -> bool {
+        let entries: Vec<Arc<ActorEntry>> = self.entries.lock().values().cloned().collect();
+        entries
+            .iter()
+            .fold(false, |has_work, entry| entry.run() || has_work)
+    }
+}
+
+/// The actors of a worker thread.
+pub(crate) struct LocalActors {
+    thread_id: u32,
+    version: Option<u64>,
+    entries: Vec<Arc<ActorEntry>>,
+    round: u32,
+}
+
+impl LocalActors {
+    pub fn new(thread_id: usize) -> Self {
+        Self {
+            thread_id: thread_id as u32,
+            version: None,
+            entries: Vec::new(),
+            round: 0,
+        }
+    }
+
+    /// Run the actors that are due in this round. Returns whether any of them
+    /// has done some work.
+    pub fn run(&mut self, registry: &ActorRegistry) -> bool {
+        let version = registry.version.load(Ordering::Acquire);
+        if self.version != Some(version) {
+            self.refresh(registry, version);
+        }
+
+        let round = self.round;
+        self.round = self.round.wrapping_add(1);
+        self.entries.iter().fold(false, |has_work, entry| {
+            if round % entry.interval != 0 || entry.is_removed.load(Ordering::Acquire) {
+                return has_work;
+            }
+            entry.run() || has_work
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn refresh(&mut self, registry: &ActorRegistry, version: u64) {
+        let thread_id = self.thread_id;
+        self.entries = registry
+            .entries
+            .lock()
+            .values()
+            .filter(|entry| entry.thread_id.map_or(true, |id| id == thread_id))
+            .cloned()
+            .collect();
+        self.version = Some(version);
+    }
+}
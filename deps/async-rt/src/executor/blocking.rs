@@ -0,0 +1,197 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+
+use crate::executor::park::ParkerFactory;
+use crate::executor::unwind;
+use crate::executor::{Parker, Unwinder};
+use crate::prelude::*;
+
+/// A function that spawns a thread with the given name.
+pub(crate) type ThreadSpawner =
+    Arc<dyn Fn(String, Box<dyn FnOnce() + Send>) -> Result<()> + Send + Sync + 'static>;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of threads that run blocking jobs, so that the worker threads of an
+/// executor never block on them.
+///
+/// The threads are spawned on demand, up to a maximum, and are kept until the
+/// pool is shut down. With SGX, the threads are enclave threads as long as the
+/// thread spawner creates them so.
+///
+/// A thread that is taken down by a panic in a job (i.e., one that cannot be
+/// caught) is replaced, so the pool neither loses its threads nor miscounts them.
+pub(crate) struct BlockingPool {
+    state: Mutex<PoolState>,
+    max_threads: u32,
+    thread_name: String,
+    spawn_thread: ThreadSpawner,
+    new_parker: ParkerFactory,
+    unwinder: Option<Arc<dyn Unwinder>>,
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    num_threads: u32,
+    // The idle threads, each of which is identified by its index
+    idle_threads: Vec<(u32, Arc<dyn Parker>)>,
+    is_shutdown: bool,
+}
+
+impl BlockingPool {
+    pub fn new(
+        max_threads: u32,
+        thread_name: String,
+        spawn_thread: ThreadSpawner,
+        new_parker: ParkerFactory,
+        unwinder: Option<Arc<dyn Unwinder>>,
+    ) -> Self {
+        let state = Mutex::new(PoolState {
+            jobs: VecDeque::new(),
+            num_threads: 0,
+            idle_threads: Vec::new(),
+            is_shutdown: false,
+        });
+        Self {
+            state,
+            max_threads,
+            thread_name,
+            spawn_thread,
+            new_parker,
+            unwinder,
+        }
+    }
+
+    /// Run a job on one of the threads. Returns an error if the pool is shut down.
+    ///
+    /// A panic in the job is confined to the job.
+    pub fn submit(self: &Arc<Self>, job: impl FnOnce() + Send + 'static) -> Result<()> {
+        let mut state = self.state.lock();
+        if state.is_shutdown {
+            return Err("a shut-down runtime cannot run blocking jobs");
+        }
+        state.jobs.push_back(Box::new(job));
+
+        if let Some((_, parker)) = state.idle_threads.pop() {
+            drop(state);
+            parker.unpark();
+        } else if state.num_threads < self.max_threads {
+            let thread_idx = state.num_threads;
+            state.num_threads += 1;
+            drop(state);
+            self.spawn_thread(thread_idx);
+        }
+        // Otherwise, one of the busy threads will pick up the job later
+        Ok(())
+    }
+
+    fn spawn_thread(self: &Arc<Self>, thread_idx: u32) {
+        let thread_name = format!("{}-blocking-{}", self.thread_name, thread_idx);
+        let pool = self.clone();
+        let thread = Box::new(move || {
+            unwind::with_unwinder(pool.unwinder.as_ref(), || pool.run_jobs(thread_idx))
+        });
+        if (self.spawn_thread)(thread_name, thread).is_err() {
+            self.on_thread_exit();
+        }
+    }
+
+    /// Account for a thread that has exited or failed to start.
+    fn on_thread_exit(&self) {
+        let mut state = self.state.lock();
+        state.num_threads -= 1;
+        // The remaining threads pick up the queued jobs once they are free. But
+        // without any threads, the jobs would never be run. Dropping a job joins
+        // it as cancelled.
+        if state.num_threads == 0 {
+            let jobs: Vec<Job> = state.jobs.drain(..).collect();
+            drop(state);
+            drop(jobs);
+        }
+    }
+
+    fn run_jobs(self: &Arc<Self>, thread_idx: u32) {
+        // The guard is dropped last, i.e., after the parker, whether the thread
+        // exits normally or is unwinding from a job
+        struct ExitGuard<'a> {
+            pool: &'a Arc<BlockingPool>,
+            thread_idx: u32,
+            is_done: bool,
+        }
+
+        impl Drop for ExitGuard<'_> {
+            fn drop(&mut self) {
+                if self.is_done {
+                    self.pool.on_thread_exit();
+                    return;
+                }
+
+                // Replace the thread, which keeps its index and its place in
+                // the count, so that the queued jobs are still run
+                log::error!("a blocking thread is taken down by a panic");
+                self.pool.spawn_thread(self.thread_idx);
+            }
+        }
+
+        let mut guard = ExitGuard {
+            pool: self,
+            thread_idx,
+            is_done: false,
+        };
+        let parker: Arc<dyn Parker> = Arc::from((self.new_parker)());
+        loop {
+            let job = {
+                let mut state = self.state.lock();
+                match state.jobs.pop_front() {
+                    Some(job) => {
+                        // The thread may have taken the job after a spurious
+                        // wakeup, so it must no longer be unparked for new jobs
+                        state.idle_threads.retain(|(idx, _)| *idx != thread_idx);
+                        Some(job)
+                    }
+                    None if state.is_shutdown => {
+                        guard.is_done = true;
+                        return;
+                    }
+                    None => {
+                        // The thread may have been woken up spuriously
+                        let is_idle = state.idle_threads.iter().any(|(idx, _)| *idx == thread_idx);
+                        if !is_idle {
+                            state.idle_threads.push((thread_idx, parker.clone()));
+                        }
+                        None
+                    }
+                }
+            };
+
+            match job {
+                Some(job) => {
+                    let _ = unwind::catch_unwind(job);
+                }
+                None => parker.park(None),
+            }
+        }
+    }
+
+    /// Returns the number of the threads, which drops to zero once the pool is
+    /// shut down and all of its jobs are done.
+    pub fn num_threads(&self) -> u32 {
+        self.state.lock().num_threads
+    }
+
+    /// Refuse new jobs and have the threads exit once the remaining jobs are done.
+    ///
+    /// This does not wait for the threads. The runtime does so by waiting for
+    /// `num_threads` to drop to zero.
+    pub fn shutdown(&self) {
+        let idle_threads = {
+            let mut state = self.state.lock();
+            state.is_shutdown = true;
+            core::mem::replace(&mut state.idle_threads, Vec::new())
+        };
+        for (_, parker) in idle_threads {
+            parker.unpark();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::prelude::*;
+
+/// A function that pins the current thread to the CPU of the given index.
+pub(crate) type CpuPinner = Arc<dyn Fn(u32) -> Result<()> + Send + Sync + 'static>;
+
+/// The CPUs that the worker threads of an executor are pinned to.
+///
+/// `Affinity` maps tasks to worker threads, which are mapped to CPUs here, so
+/// that a task bound to a worker thread also stays on a CPU.
+pub(crate) struct WorkerCpus {
+    // The CPU of each worker thread, indexed by its thread ID
+    cpus: Vec<u32>,
+    pin: CpuPinner,
+}
+
+impl WorkerCpus {
+    pub fn new(cpus: Vec<u32>, pin: CpuPinner) -> Self {
+        Self { cpus, pin }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cpus.len()
+    }
+
+    /// Returns the CPU of the worker thread of the given index.
+    pub fn get(&self, thread_id: usize) -> Option<u32> {
+        self.cpus.get(thread_id).copied()
+    }
+
+    /// Pin the current thread, which is the worker thread of the given index,
+    /// to its CPU.
+    ///
+    /// A failure is only logged, since the thread can still run tasks, just
+    /// without the cache locality.
+    pub fn pin_current(&self, thread_id: usize) {
+        let cpu = match self.get(thread_id) {
+            Some(cpu) => cpu,
+            None => return,
+        };
+        if let Err(e) = (self.pin)(cpu) {
+            log::warn!(
+                "failed to pin worker thread {} to CPU {}: {}",
+                thread_id,
+                cpu,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(all(any(test, feature = "std"), target_os = "linux"))]
+pub(crate) fn default_cpu_pinner() -> Result<CpuPinner> {
+    Ok(Arc::new(|cpu| host::sched_setaffinity(cpu)))
+}
+
+#[cfg(not(all(any(test, feature = "std"), target_os = "linux")))]
+pub(crate) fn default_cpu_pinner() -> Result<CpuPinner> {
+    Err("a CPU pinner is required without std on Linux")
+}
+
+#[cfg(all(any(test, feature = "std"), target_os = "linux"))]
+mod host {
+    use crate::prelude::*;
+
+    // The same size as `cpu_set_t` of glibc
+    const CPU_SET_WORDS: usize = 1024 / 64;
+
+    extern "C" {
+        // std links against the libc, which provides this
+        #[link_name = "sched_setaffinity"]
+        fn libc_sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+    }
+
+    /// Pin the current thread to the given CPU.
+    pub fn sched_setaffinity(cpu: u32) -> Result<()> {
+        let cpu = cpu as usize;
+        if cpu >= CPU_SET_WORDS * 64 {
+            return Err("the CPU index is out of range");
+        }
+        let mut mask = [0_u64; CPU_SET_WORDS];
+        mask[cpu / 64] |= 1 << (cpu % 64);
+
+        // A pid of zero means the calling thread
+        let ret =
+            unsafe { libc_sched_setaffinity(0, core::mem::size_of_val(&mask), mask.as_ptr()) };
+        if ret != 0 {
+            return Err("sched_setaffinity failed");
+        }
+        Ok(())
+    }
+}
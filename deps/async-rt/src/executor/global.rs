@@ -0,0 +1,104 @@
+//! The global executor, which is the API before `Runtime` was introduced.
+//!
+//! The global executor is created on first use and has no worker threads of its
+//! own: the threads that call `run_tasks` become its worker threads. New code
+//! should build a `Runtime` instead.
+
+use crate::executor::{Executor, Parker};
+use crate::prelude::*;
+use crate::runtime::{Builder, Handle};
+
+pub const DEFAULT_PARALLELISM: u32 = 1;
+pub const DEFAULT_MAX_QUEUED_TASKS: u32 = 1_000;
+
+static PARALLELISM: AtomicU32 = AtomicU32::new(DEFAULT_PARALLELISM);
+static MAX_QUEUED_TASKS: AtomicU32 = AtomicU32::new(DEFAULT_MAX_QUEUED_TASKS);
+
+lazy_static! {
+    static ref EXECUTOR: Arc<Executor> = {
+        let builder = Builder::new()
+            .worker_threads(PARALLELISM.load(Ordering::Relaxed))
+            .max_queued_tasks(MAX_QUEUED_TASKS.load(Ordering::Relaxed))
+            .thread_name("async-rt-global");
+        // Without std, blocking jobs are refused since no threads can be spawned
+        #[cfg(not(any(test, feature = "std")))]
+        let builder = builder.thread_spawner(|_, _| Err("the global executor cannot spawn threads"));
+        builder.build_executor().unwrap()
+    };
+}
+
+/// Returns the handle to the global executor, which is created on first use.
+pub(crate) fn global_handle() -> Handle {
+    Handle::new(EXECUTOR.clone())
+}
+
+/// Returns the number of worker threads that the global executor has, or will
+/// have when it is created.
+pub(crate) fn global_parallelism() -> u32 {
+    PARALLELISM.load(Ordering::Relaxed)
+}
+
+/// Set the number of worker threads of the global executor, which only takes
+/// effect before the global executor is used.
+#[deprecated(note = "use `Builder::worker_threads` instead")]
+pub fn set_parallelism(parallelism: u32) -> Result<()> {
+    if parallelism == 0 {
+        return Err("invalid argument");
+    }
+
+    PARALLELISM.store(parallelism, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Set the capacity of the run queue of each thread of the global executor,
+/// which only takes effect before the global executor is used.
+#[deprecated(note = "use `Builder::max_queued_tasks` instead")]
+pub fn set_max_queued_tasks(max_queued_tasks: u32) -> Result<()> {
+    if max_queued_tasks == 0 {
+        return Err("invalid argument");
+    }
+
+    MAX_QUEUED_TASKS.store(max_queued_tasks, Ordering::Relaxed);
+    Ok(())
+}
+
+#[deprecated(note = "use `Handle::parallelism` instead")]
+pub fn parallelism() -> u32 {
+    EXECUTOR.parallelism()
+}
+
+/// Run the tasks of the global executor on the current thread until it is shut
+/// down.
+///
+/// # Panics
+///
+/// This function panics if called by more threads than the parallelism.
+#[deprecated(note = "use `Runtime`, which starts its own worker threads, instead")]
+pub fn run_tasks() {
+    EXECUTOR.run_tasks(EXECUTOR.new_parker())
+}
+
+#[deprecated(note = "use `Runtime` with `Builder::parker` instead")]
+pub fn run_tasks_with_parker(parker: impl Parker + 'static) {
+    EXECUTOR.run_tasks(Box::new(parker))
+}
+
+/// Register an actor, which is run repeatedly by every thread of the global
+/// executor.
+#[deprecated(note = "use `Builder::actor` or `Handle::register_actor` instead")]
+pub fn register_actor(actor: impl Fn() -> bool + Send + 'static) {
+    EXECUTOR
+        .register_actor(actor.into())
+        .expect("an actor of every thread is always accepted");
+}
+
+#[deprecated(note = "use `Handle::unpark_all` instead")]
+pub fn unpark_all() {
+    EXECUTOR.unpark_all()
+}
+
+/// Shut down the global executor, after which the threads in `run_tasks` return.
+#[deprecated(note = "drop the `Runtime` or use `Runtime::shutdown_graceful` instead")]
+pub fn shutdown() {
+    EXECUTOR.shutdown()
+}
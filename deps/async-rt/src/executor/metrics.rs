@@ -0,0 +1,125 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::time::Clock;
+
+/// A snapshot of the metrics of a runtime.
+#[derive(Debug, Clone)]
+pub struct RuntimeMetrics {
+    /// The number of tasks that have been spawned but not finished yet.
+    pub num_tasks: usize,
+    /// The number of tasks in the global overflow queue.
+    pub overflow_queue_depth: usize,
+    /// The metrics of each worker thread, indexed by thread ID.
+    pub workers: Vec<WorkerMetrics>,
+}
+
+/// A snapshot of the metrics of a worker thread.
+///
+/// All counters are accumulated since the runtime was built. The times are
+/// measured with the clock of the timers, so they stay zero without a clock or
+/// with `Builder::timing_metrics(false)`.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerMetrics {
+    /// The number of times that a task has been polled.
+    pub tasks_polled: u64,
+    /// The number of tasks that have been stolen from the sibling threads.
+    pub tasks_stolen: u64,
+    /// The total time spent in polling tasks.
+    pub poll_time: Duration,
+    /// The number of rounds that the thread has busy-waited for work.
+    pub idle_spins: u64,
+    /// The number of times that the thread has been parked.
+    pub num_parks: u64,
+    /// The total time spent in running actors.
+    pub actor_run_time: Duration,
+    /// The number of tasks in the run queue of the thread.
+    pub queue_depth: usize,
+}
+
+/// The counters of a worker thread, which are only updated by the thread itself.
+pub(crate) struct WorkerCounters {
+    tasks_polled: AtomicU64,
+    tasks_stolen: AtomicU64,
+    poll_nanos: AtomicU64,
+    idle_spins: AtomicU64,
+    num_parks: AtomicU64,
+    actor_nanos: AtomicU64,
+}
+
+impl WorkerCounters {
+    pub fn new() -> Self {
+        Self {
+            tasks_polled: AtomicU64::new(0),
+            tasks_stolen: AtomicU64::new(0),
+            poll_nanos: AtomicU64::new(0),
+            idle_spins: AtomicU64::new(0),
+            num_parks: AtomicU64::new(0),
+            actor_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn on_poll(&self, stopwatch: &Stopwatch) {
+        increase(&self.tasks_polled, 1);
+        increase(&self.poll_nanos, stopwatch.elapsed_nanos());
+    }
+
+    pub fn on_steal(&self) {
+        increase(&self.tasks_stolen, 1);
+    }
+
+    pub fn on_idle_spin(&self) {
+        increase(&self.idle_spins, 1);
+    }
+
+    pub fn on_park(&self) {
+        increase(&self.num_parks, 1);
+    }
+
+    pub fn on_actors(&self, stopwatch: &Stopwatch) {
+        increase(&self.actor_nanos, stopwatch.elapsed_nanos());
+    }
+
+    pub fn snapshot(&self, queue_depth: usize) -> WorkerMetrics {
+        WorkerMetrics {
+            tasks_polled: self.tasks_polled.load(Ordering::Relaxed),
+            tasks_stolen: self.tasks_stolen.load(Ordering::Relaxed),
+            poll_time: Duration::from_nanos(self.poll_nanos.load(Ordering::Relaxed)),
+            idle_spins: self.idle_spins.load(Ordering::Relaxed),
+            num_parks: self.num_parks.load(Ordering::Relaxed),
+            actor_run_time: Duration::from_nanos(self.actor_nanos.load(Ordering::Relaxed)),
+            queue_depth,
+        }
+    }
+}
+
+// Only the owner thread writes a counter, so a load and a store are enough
+fn increase(counter: &AtomicU64, n: u64) {
+    let value = counter.load(Ordering::Relaxed);
+    counter.store(value.wrapping_add(n), Ordering::Relaxed);
+}
+
+/// Measures the time elapsed since its creation, if enabled and a clock is available.
+pub(crate) struct Stopwatch {
+    start: Option<(&'static dyn Clock, Duration)>,
+}
+
+impl Stopwatch {
+    pub fn start(is_enabled: bool) -> Self {
+        if !is_enabled {
+            return Self { start: None };
+        }
+        let start = crate::time::clock().map(|clock| (clock, clock.now()));
+        Self { start }
+    }
+
+    pub fn elapsed_nanos(&self) -> u64 {
+        match self.start {
+            None => 0,
+            Some((clock, start)) => clock
+                .now()
+                .checked_sub(start)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+        }
+    }
+}
@@ -0,0 +1,972 @@
+use alloc::collections::VecDeque;
+use core::cell::{Cell, UnsafeCell};
+use core::ptr;
+use core::time::Duration;
+
+use flume::{Receiver, Sender, TrySendError};
+use futures::task::{waker_ref, ArcWake};
+
+use crate::prelude::*;
+use crate::sched::Priority;
+use crate::sim::Simulation;
+use crate::task::{JoinError, Task, TaskId};
+use crate::time::Timers;
+
+pub use self::actor::{Actor, ActorId};
+pub use self::global::{
+    parallelism, register_actor, run_tasks, run_tasks_with_parker, set_max_queued_tasks,
+    set_parallelism, shutdown, unpark_all, DEFAULT_MAX_QUEUED_TASKS, DEFAULT_PARALLELISM,
+};
+pub use self::metrics::{RuntimeMetrics, WorkerMetrics};
+pub use self::park::{Parker, SpinParker};
+pub use self::unwind::Unwinder;
+#[cfg(any(test, feature = "std"))]
+pub use self::park::ThreadParker;
+
+pub(crate) use self::blocking::{BlockingPool, ThreadSpawner};
+pub(crate) use self::cpus::{default_cpu_pinner, CpuPinner, WorkerCpus};
+pub(crate) use self::global::{global_handle, global_parallelism};
+pub(crate) use self::park::{default_parker, ParkerFactory};
+pub(crate) use self::unwind::{panicking, with_unwinder};
+
+use self::actor::{ActorRegistry, LocalActors};
+use self::metrics::{Stopwatch, WorkerCounters};
+use self::park::ParkSlot;
+use self::registry::TaskRegistry;
+
+mod actor;
+mod blocking;
+mod cpus;
+mod global;
+mod metrics;
+mod park;
+mod registry;
+mod unwind;
+
+/// Returns the executor that the current thread works for, if any.
+pub(crate) fn current() -> Option<Arc<Executor>> {
+    let ptr = CURRENT.get();
+    if ptr == ptr::null() {
+        return None;
+    }
+    let executor = unsafe { Arc::from_raw(ptr) };
+    Arc::into_raw(executor.clone());
+    Some(executor)
+}
+
+/// Returns the index of the current thread among the worker threads of its
+/// executor, if it is a worker thread.
+pub(crate) fn current_thread_id() -> Option<usize> {
+    if CURRENT.get() == ptr::null() {
+        return None;
+    }
+    Some(THREAD_ID.get())
+}
+
+fn set_current(executor: Arc<Executor>) {
+    let last_ptr = CURRENT.replace(Arc::into_raw(executor));
+    free_executor_ptr(last_ptr);
+}
+
+fn reset_current() {
+    let last_ptr = CURRENT.replace(ptr::null());
+    free_executor_ptr(last_ptr);
+}
+
+fn free_executor_ptr(ptr: *const Executor) {
+    if ptr != ptr::null() {
+        let executor = unsafe { Arc::from_raw(ptr) };
+        drop(executor);
+    }
+}
+
+#[thread_local]
+static CURRENT: Cell<*const Executor> = Cell::new(ptr::null_mut());
+
+// The index of the current thread among the worker threads of the current executor
+#[thread_local]
+static THREAD_ID: Cell<usize> = Cell::new(0);
+
+// The number of the nested calls of `Executor::block_on` that run tasks on the current thread
+#[thread_local]
+static BLOCK_ON_DEPTH: Cell<u32> = Cell::new(0);
+
+/// The max number of the nested calls of `Executor::block_on` that run tasks,
+/// beyond which the worker thread parks instead of taking up more stack.
+const MAX_BLOCK_ON_DEPTH: u32 = 16;
+
+/// The configuration of an executor, which is given by `runtime::Builder`.
+pub(crate) struct ExecutorConfig {
+    pub parallelism: u32,
+    pub max_queued_tasks: u32,
+    pub actors: Vec<Actor>,
+    pub blocking_pool: BlockingPool,
+    pub simulation: Option<Simulation>,
+    pub worker_cpus: Option<WorkerCpus>,
+    pub new_parker: ParkerFactory,
+    // Whether to measure the time spent in polling tasks and running actors
+    pub timing_metrics: bool,
+}
+
+pub(crate) struct Executor {
+    parallelism: u32,
+    run_queues: Vec<RunQueue>,
+    local_queues: Vec<LocalQueue>,
+    overflow_queue: (Sender<Arc<Task>>, Receiver<Arc<Task>>),
+    park_slots: Vec<ParkSlot>,
+    counters: Vec<WorkerCounters>,
+    next_run_queue_id: AtomicU32,
+    tasks: TaskRegistry,
+    is_shutdown: AtomicBool,
+    actors: ActorRegistry,
+    blocking_pool: Arc<BlockingPool>,
+    // The state of a deterministic executor, which has a single worker thread
+    simulation: Option<Simulation>,
+    // The CPUs that the worker threads are pinned to, if any
+    worker_cpus: Option<WorkerCpus>,
+    // The timers of the tasks that run on this executor
+    timers: Timers,
+    // Creates the parkers of the threads that block on the executor
+    new_parker: ParkerFactory,
+    timing_metrics: bool,
+}
+
+impl Executor {
+    pub fn new(config: ExecutorConfig) -> Result<Self> {
+        let ExecutorConfig {
+            parallelism,
+            max_queued_tasks,
+            actors,
+            blocking_pool,
+            simulation,
+            worker_cpus,
+            new_parker,
+            timing_metrics,
+        } = config;
+        if parallelism == 0 || max_queued_tasks == 0 {
+            return Err("invalid argument");
+        }
+        if simulation.is_some() && parallelism != 1 {
+            return Err("a deterministic executor must have exactly one worker thread");
+        }
+        if worker_cpus
+            .as_ref()
+            .map_or(false, |cpus| cpus.len() != parallelism as usize)
+        {
+            return Err("the number of CPUs must match the number of worker threads");
+        }
+
+        let run_queues = (0..parallelism)
+            .map(|_| RunQueue::new(max_queued_tasks as usize))
+            .collect();
+        let local_queues = (0..parallelism).map(|_| LocalQueue::new()).collect();
+        let overflow_queue = flume::unbounded();
+        let park_slots = (0..parallelism).map(|_| ParkSlot::new()).collect();
+        let counters = (0..parallelism).map(|_| WorkerCounters::new()).collect();
+
+        let tasks = TaskRegistry::new();
+        let is_shutdown = AtomicBool::new(false);
+        let next_run_queue_id = AtomicU32::new(0);
+        let actors = {
+            let registry = ActorRegistry::new();
+            for actor in actors {
+                registry.register(actor, parallelism)?;
+            }
+            registry
+        };
+        let blocking_pool = Arc::new(blocking_pool);
+        let timers = Timers::new();
+
+        let new_self = Self {
+            parallelism,
+            run_queues,
+            local_queues,
+            overflow_queue,
+            park_slots,
+            counters,
+            next_run_queue_id,
+            tasks,
+            is_shutdown,
+            actors,
+            blocking_pool,
+            simulation,
+            worker_cpus,
+            timers,
+            new_parker,
+            timing_metrics,
+        };
+        Ok(new_self)
+    }
+
+    pub fn parallelism(&self) -> u32 {
+        self.parallelism
+    }
+
+    pub fn blocking_pool(&self) -> &Arc<BlockingPool> {
+        &self.blocking_pool
+    }
+
+    /// Create a parker for the current thread.
+    pub fn new_parker(&self) -> Box<dyn Parker> {
+        (self.new_parker)()
+    }
+
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    /// Returns the state of the simulation if the executor is deterministic.
+    pub fn simulation(&self) -> Option<&Simulation> {
+        self.simulation.as_ref()
+    }
+
+    /// Returns the CPU that the worker thread of the given index is pinned to.
+    pub fn worker_cpu(&self, thread_id: usize) -> Option<u32> {
+        self.worker_cpus
+            .as_ref()
+            .and_then(|cpus| cpus.get(thread_id))
+    }
+
+    /// Run tasks on the current thread until the executor is shut down.
+    pub fn run_tasks(self: &Arc<Self>, parker: Box<dyn Parker>) {
+        let run_queue_id = self.next_run_queue_id.fetch_add(1, Ordering::Relaxed);
+        assert!(run_queue_id < self.parallelism);
+        let thread_id = run_queue_id as usize;
+        let park_slot = &self.park_slots[thread_id];
+        park_slot.init(parker);
+
+        set_current(self.clone());
+        THREAD_ID.set(thread_id);
+        if let Some(worker_cpus) = self.worker_cpus.as_ref() {
+            worker_cpus.pin_current(thread_id);
+        }
+        self.do_run_tasks(thread_id, || false);
+        self.teardown_remaining_tasks(thread_id);
+        reset_current();
+    }
+
+    /// Tear down the tasks that a worker thread leaves behind as it exits, which
+    /// are joined with `JoinError::Shutdown`.
+    ///
+    /// These are the tasks in the queues of the thread, as well as its idle
+    /// local tasks, whose futures may only be dropped on the thread. There are
+    /// none unless the executor is shut down without waiting for its tasks,
+    /// e.g., from one of its worker threads.
+    fn teardown_remaining_tasks(&self, thread_id: usize) {
+        while let Some(task) = self.pop_remaining_task(thread_id) {
+            self.teardown_on_shutdown(&task);
+        }
+        for task in self.live_tasks() {
+            if task.sched_info().local_thread_id() == Some(thread_id as u32) {
+                self.teardown_on_shutdown(&task);
+            }
+        }
+    }
+
+    /// Dequeue any task that the exiting thread is to tear down.
+    fn pop_remaining_task(&self, thread_id: usize) -> Option<Arc<Task>> {
+        if let Some(simulation) = &self.simulation {
+            return simulation.pop_task();
+        }
+
+        self.pop_local_task(thread_id)
+            .or_else(|| self.run_queues[thread_id].pop(0))
+            .or_else(|| self.overflow_queue.1.try_recv().ok())
+    }
+
+    fn teardown_on_shutdown(&self, task: &Arc<Task>) {
+        task.set_aborted(JoinError::Shutdown);
+        task.transition_to_completed();
+        // Safety. The task is either dequeued or local to this thread, which
+        // runs no tasks any more.
+        let future = match unsafe { task.take_future() } {
+            None => return,
+            Some(future) => future,
+        };
+        crate::task::set_current(task.clone());
+        self.teardown_task(task, future);
+        crate::task::reset_current();
+    }
+
+    /// Block the current thread until a future completes.
+    ///
+    /// On a worker thread of the executor, the thread keeps running other tasks
+    /// until the future is woken up. Otherwise, or if the calls are nested too
+    /// deeply, the thread is parked with a parker of the executor.
+    pub fn block_on<F: Future + Unpin>(self: &Arc<Self>, future: F) -> F::Output {
+        // A shut-down executor runs no tasks, so there is nothing to do but wait
+        let thread_id = match self.current_thread_id() {
+            Some(thread_id) if BLOCK_ON_DEPTH.get() < MAX_BLOCK_ON_DEPTH && !self.is_shutdown() => {
+                thread_id
+            }
+            _ => return self.park_on(future),
+        };
+
+        BLOCK_ON_DEPTH.set(BLOCK_ON_DEPTH.get() + 1);
+        let output = self.run_tasks_on(thread_id, future);
+        BLOCK_ON_DEPTH.set(BLOCK_ON_DEPTH.get() - 1);
+        output
+    }
+
+    /// Block the current thread until a future completes by parking it.
+    fn park_on<F: Future + Unpin>(&self, mut future: F) -> F::Output {
+        let waker = Arc::new(ParkerWaker(self.new_parker()));
+        let waker_ref = waker_ref(&waker);
+        let context = &mut Context::from_waker(&*waker_ref);
+        loop {
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(context) {
+                return output;
+            }
+            waker.0.park(None);
+        }
+    }
+
+    /// Block the current worker thread until a future completes by running
+    /// other tasks.
+    fn run_tasks_on<F: Future + Unpin>(
+        self: &Arc<Self>,
+        thread_id: usize,
+        mut future: F,
+    ) -> F::Output {
+        // The tasks run below replace the current task, which is restored at last
+        let current_task = crate::task::try_current();
+        let waker = Arc::new(WorkerWaker {
+            executor: self.clone(),
+            thread_id,
+            is_woken: AtomicBool::new(false),
+        });
+        let waker_ref = waker_ref(&waker);
+        let context = &mut Context::from_waker(&*waker_ref);
+        let output = loop {
+            waker.is_woken.store(false, Ordering::SeqCst);
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(context) {
+                break output;
+            }
+            // Once shut down, the executor would return at once without parking
+            if self.is_shutdown() {
+                break self.park_on(&mut future);
+            }
+            self.do_run_tasks(thread_id, || waker.is_woken.load(Ordering::SeqCst));
+        };
+        if let Some(task) = current_task {
+            crate::task::set_current(task);
+        }
+        output
+    }
+
+    /// Returns the index of the current thread if it is a worker thread of the executor.
+    pub fn current_thread_id(&self) -> Option<usize> {
+        if CURRENT.get() != self as *const Self {
+            return None;
+        }
+        Some(THREAD_ID.get())
+    }
+
+    /// Run tasks on the current worker thread until the executor is shut down
+    /// or `should_stop` returns true.
+    fn do_run_tasks(&self, thread_id: usize, should_stop: impl Fn() -> bool) {
+        let park_slot = &self.park_slots[thread_id];
+        let counters = &self.counters[thread_id];
+        let mut actors = LocalActors::new(thread_id);
+        // The number of consecutive rounds in which the thread has found no work
+        const MAX_IDLE_ROUNDS: u32 = 1_000;
+        // The max duration that actors and timers may be left unattended by a parked thread
+        const PARK_TIMEOUT: Duration = Duration::from_millis(1);
+        // The overflow queue is checked first once in a while so that the
+        // tasks in it cannot be starved by busy run queues
+        const OVERFLOW_CHECK_INTERVAL: u32 = 61;
+        let mut idle_rounds: u32 = 0;
+        let mut tick: u32 = 0;
+        loop {
+            // The tasks left in the queues are torn down as the worker exits
+            if should_stop() || self.is_shutdown() {
+                return;
+            }
+
+            let stopwatch = Stopwatch::start(self.timing_metrics);
+            let mut has_actor_work = actors.run(&self.actors);
+            counters.on_actors(&stopwatch);
+            has_actor_work |= self.timers.fire();
+            has_actor_work |= crate::time::fire_global_timers();
+
+            let task = {
+                let task_res = if tick % OVERFLOW_CHECK_INTERVAL == 0 {
+                    self.pop_overflowed_task(thread_id)
+                        .or_else(|| self.pop_task(thread_id, tick))
+                } else {
+                    self.pop_task(thread_id, tick)
+                        .or_else(|| self.pop_overflowed_task(thread_id))
+                };
+                tick = tick.wrapping_add(1);
+
+                match task_res.or_else(|| self.steal_task(thread_id)) {
+                    None if has_actor_work => {
+                        idle_rounds = 0;
+                        continue;
+                    }
+                    None if idle_rounds < MAX_IDLE_ROUNDS => {
+                        idle_rounds += 1;
+                        counters.on_idle_spin();
+                        core::sync::atomic::spin_loop_hint();
+                        continue;
+                    }
+                    None => {
+                        idle_rounds = 0;
+                        let has_timers =
+                            self.timers.has_timers() || crate::time::has_global_timers();
+                        let timeout = if !actors.is_empty() || has_timers {
+                            Some(PARK_TIMEOUT)
+                        } else {
+                            None
+                        };
+                        counters.on_park();
+                        park_slot.park(timeout, || {
+                            should_stop() || self.has_work_for(thread_id)
+                        });
+                        continue;
+                    }
+                    Some(task) => task,
+                }
+            };
+            idle_rounds = 0;
+
+            // Safety. A task is in the run queues at most once, and only when it
+            // is not running. So this thread is the only one that runs it now.
+            let mut future =
+                unsafe { task.take_future() }.expect("a queued task must have its future");
+
+            crate::task::set_current(task.clone());
+            task.transition_to_running();
+
+            if task.is_aborted() {
+                // Dropping the future joins the task as cancelled
+                task.transition_to_completed();
+                self.teardown_task(&task, future);
+            } else {
+                let waker = waker_ref(&task);
+                let context = &mut Context::from_waker(&*waker);
+                let stopwatch = Stopwatch::start(self.timing_metrics);
+                let poll_res = unwind::catch_unwind(|| {
+                    crate::sched::coop::with_budget(|| future.as_mut().poll(context))
+                });
+                counters.on_poll(&stopwatch);
+                match poll_res {
+                    Ok(Poll::Pending) => {
+                        // The future must be in place before the task can be
+                        // woken up and dequeued by another thread
+                        unsafe { task.put_future(future) };
+                        if task.transition_to_idle() {
+                            self.enqueue_task(task.clone());
+                        }
+                    }
+                    Ok(Poll::Ready(())) => {
+                        task.transition_to_completed();
+                        self.teardown_task(&task, future);
+                    }
+                    Err(()) => {
+                        log::error!("{:?} panicked", task);
+                        // Dropping the future joins the task as panicked
+                        task.set_panicked();
+                        task.transition_to_completed();
+                        self.teardown_task(&task, future);
+                    }
+                }
+            }
+
+            crate::task::reset_current();
+        }
+    }
+
+    /// Release the resources of a task that will never be polled again.
+    ///
+    /// Both the future and the task-locals are dropped here, with the task being
+    /// the current one. Any panic while dropping them is confined to the task.
+    fn teardown_task(&self, task: &Arc<Task>, future: BoxFuture<'static, ()>) {
+        let _ = unwind::catch_unwind(move || drop(future));
+        let _ = unwind::catch_unwind(|| unsafe { task.locals().clear() });
+        self.unregister_task(task.tid());
+    }
+
+    /// Accept a new task.
+    ///
+    /// After the executor is closed, the task is dropped immediately without
+    /// being run, which joins the task with `JoinError::Shutdown`.
+    pub fn accept_task(&self, task: Arc<Task>) {
+        if !self.register_task(&task) {
+            task.set_aborted(JoinError::Shutdown);
+            task.transition_to_completed();
+            // Safety. The task has never been enqueued.
+            let future = unsafe { task.take_future() };
+            drop(future);
+            return;
+        }
+
+        if task.transition_to_queued() {
+            self.enqueue_task(task);
+        }
+    }
+
+    /// Accept a task only if it fits in the run queue of its target thread.
+    pub fn try_accept_task(&self, task: Arc<Task>) -> Result<()> {
+        if !self.register_task(&task) {
+            return Err("a shut-down executor cannot spawn new tasks");
+        }
+        // A new task is always idle, so it is queued for sure
+        task.transition_to_queued();
+        if self.simulation.is_some() {
+            self.enqueue_task(task);
+            return Ok(());
+        }
+
+        let thread_id = self.pick_thread_for(&task);
+        let is_stealable = task.sched_info().affinity().read().is_full();
+        if let Err(task) = self.run_queues[thread_id].try_push(task) {
+            self.unregister_task(task.tid());
+            return Err("too many tasks enqueued");
+        }
+        self.unpark_for(thread_id, is_stealable);
+        Ok(())
+    }
+
+    /// Add a task to the live tasks unless the executor is closed.
+    fn register_task(&self, task: &Arc<Task>) -> bool {
+        self.tasks.register(task)
+    }
+
+    /// Returns the task of the given ID if it has not been torn down yet.
+    pub fn find_task(&self, tid: TaskId) -> Option<Arc<Task>> {
+        self.tasks.find(tid)
+    }
+
+    pub fn unregister_task(&self, tid: TaskId) {
+        self.tasks.unregister(tid);
+    }
+
+    /// Returns the number of the tasks that have not been torn down yet.
+    pub fn num_tasks(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Refuse any new tasks from now on.
+    pub fn close(&self) {
+        self.tasks.close();
+    }
+
+    /// Returns the tasks that have not been torn down yet, in the order of their IDs.
+    pub fn live_tasks(&self) -> Vec<Arc<Task>> {
+        self.tasks.live_tasks()
+    }
+
+    /// Abort all live tasks, which are joined with `JoinError::Shutdown`.
+    pub fn cancel_tasks(&self) {
+        // Aborting a task may lock the tasks again, e.g., when it is dropped
+        for task in self.live_tasks() {
+            task.abort(JoinError::Shutdown);
+        }
+    }
+
+    /// Take a snapshot of the metrics of the executor.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        let workers = self
+            .counters
+            .iter()
+            .zip(self.run_queues.iter())
+            .map(|(counters, run_queue)| counters.snapshot(run_queue.len()))
+            .collect();
+        RuntimeMetrics {
+            num_tasks: self.num_tasks(),
+            overflow_queue_depth: self.overflow_queue.1.len(),
+            workers,
+        }
+    }
+
+    /// Reschedule a task that has been woken up.
+    ///
+    /// Unlike spawning, waking up a task is not an error after shutdown, e.g.,
+    /// when a timer fires late. The task is simply never run again.
+    pub fn wake_task(&self, task: Arc<Task>) {
+        if self.is_shutdown() {
+            return;
+        }
+
+        if task.transition_to_queued() {
+            self.enqueue_task(task);
+        }
+    }
+
+    /// Put a queued task into a run queue.
+    fn enqueue_task(&self, task: Arc<Task>) {
+        // All tasks of a deterministic executor run on its only thread
+        if let Some(simulation) = &self.simulation {
+            simulation.push_task(task);
+            self.park_slots[0].unpark();
+            return;
+        }
+
+        if let Some(thread_id) = task.sched_info().local_thread_id() {
+            self.enqueue_local_task(thread_id as usize, task);
+            return;
+        }
+
+        let thread_id = self.pick_thread_for(&task);
+        let mut is_stealable = task.sched_info().affinity().read().is_full();
+        if let Err(task) = self.run_queues[thread_id].try_push(task) {
+            // Any thread may pick up the task from the overflow queue
+            self.overflow_queue.0.send(task).unwrap();
+            is_stealable = true;
+        }
+        self.unpark_for(thread_id, is_stealable);
+    }
+
+    fn enqueue_local_task(&self, thread_id: usize, task: Arc<Task>) {
+        let local_queue = &self.local_queues[thread_id];
+        if self.current_thread_id() == Some(thread_id) {
+            // Safety. This is the owner thread of the local queue.
+            unsafe { local_queue.push(task) };
+        } else {
+            local_queue.push_remote(task);
+            self.park_slots[thread_id].unpark();
+        }
+    }
+
+    /// Unpark the thread that a task has just been enqueued for.
+    fn unpark_for(&self, thread_id: usize, is_stealable: bool) {
+        // If the target thread is busy, let an idle sibling steal the task
+        if !self.park_slots[thread_id].unpark() && is_stealable {
+            let idle_slot = self
+                .park_slots
+                .iter()
+                .find(|park_slot| park_slot.is_parked());
+            if let Some(idle_slot) = idle_slot {
+                idle_slot.unpark();
+            }
+        }
+    }
+
+    /// Dequeue a task from the run queue or the local queue of the thread.
+    fn pop_task(&self, thread_id: usize, tick: u32) -> Option<Arc<Task>> {
+        if let Some(simulation) = &self.simulation {
+            return simulation.pop_task();
+        }
+
+        // Alternate between the two queues so that neither of them can starve the other
+        if tick % 2 == 0 {
+            self.pop_local_task(thread_id)
+                .or_else(|| self.pop_queued_task(thread_id, tick))
+        } else {
+            self.pop_queued_task(thread_id, tick)
+                .or_else(|| self.pop_local_task(thread_id))
+        }
+    }
+
+    fn pop_local_task(&self, thread_id: usize) -> Option<Arc<Task>> {
+        // Safety. Only the owner thread dequeues from its local queue.
+        unsafe { self.local_queues[thread_id].pop() }
+    }
+
+    fn pop_queued_task(&self, thread_id: usize, tick: u32) -> Option<Arc<Task>> {
+        let task = self.run_queues[thread_id].pop(tick)?;
+
+        // The affinity of the task may have been changed after it was enqueued
+        if !self.can_run_on(&task, thread_id) {
+            self.enqueue_task(task);
+            return None;
+        }
+        Some(task)
+    }
+
+    /// Dequeue a task from the overflow queue for the thread.
+    fn pop_overflowed_task(&self, thread_id: usize) -> Option<Arc<Task>> {
+        let task = self.overflow_queue.1.try_recv().ok()?;
+
+        if !self.can_run_on(&task, thread_id) {
+            self.enqueue_task(task);
+            return None;
+        }
+
+        task.sched_info().set_last_thread_id(thread_id as u32);
+        Some(task)
+    }
+
+    /// Returns whether there are tasks that the thread can run or steal.
+    fn has_work_for(&self, thread_id: usize) -> bool {
+        self.is_shutdown()
+            || self
+                .simulation
+                .as_ref()
+                .map_or(false, |sim| sim.has_tasks())
+            || !self.overflow_queue.1.is_empty()
+            || self.run_queues.iter().enumerate().any(|(id, run_queue)| {
+                if id == thread_id {
+                    !run_queue.is_empty() || self.local_queues[id].has_remote()
+                } else {
+                    run_queue.has_stealable()
+                }
+            })
+    }
+
+    /// Try to steal a task from the run queues of the sibling threads.
+    ///
+    /// Only the tasks that can run on any thread are subject to stealing. And
+    /// the siblings are visited in a round-robin fashion starting from the next
+    /// thread so that the stealing pressure is spread evenly among them.
+    fn steal_task(&self, thread_id: usize) -> Option<Arc<Task>> {
+        let parallelism = self.parallelism as usize;
+        for offset in 1..parallelism {
+            let victim_id = (thread_id + offset) % parallelism;
+            let victim_queue = &self.run_queues[victim_id];
+            let task = match victim_queue.steal() {
+                None => continue,
+                Some(task) => task,
+            };
+
+            // The affinity of the task may have been changed after it was enqueued
+            if !self.can_run_on(&task, thread_id) {
+                self.enqueue_task(task);
+                continue;
+            }
+
+            task.sched_info().set_last_thread_id(thread_id as u32);
+            self.counters[thread_id].on_steal();
+            return Some(task);
+        }
+        None
+    }
+
+    /// Returns whether the task can run on the thread.
+    ///
+    /// An affinity that contains no worker threads, e.g., an empty one that is
+    /// written to the task directly, is treated as a full one, so that the task
+    /// can still run somewhere.
+    fn can_run_on(&self, task: &Task, thread_id: usize) -> bool {
+        let affinity = task.sched_info().affinity().read();
+        affinity.get(thread_id) || !(0..self.parallelism as usize).any(|id| affinity.get(id))
+    }
+
+    fn pick_thread_for(&self, task: &Arc<Task>) -> usize {
+        let parallelism = self.parallelism as usize;
+        let last_thread_id = task.sched_info().last_thread_id() as usize;
+        let affinity = task.sched_info().affinity().read();
+        // See `can_run_on` for an affinity that contains no worker threads
+        let thread_id = (0..parallelism)
+            .map(|offset| (last_thread_id + offset) % parallelism)
+            .find(|thread_id| affinity.get(*thread_id))
+            .unwrap_or(last_thread_id % parallelism);
+        drop(affinity);
+
+        task.sched_info().set_last_thread_id(thread_id as u32);
+        thread_id
+    }
+
+    pub fn shutdown(&self) {
+        self.is_shutdown.store(true, Ordering::Relaxed);
+        // The tasks that are never run again are joined with the error, no
+        // matter where they are dropped
+        for task in self.live_tasks() {
+            task.set_aborted(JoinError::Shutdown);
+        }
+        self.unpark_all();
+        self.blocking_pool.shutdown();
+    }
+
+    pub fn unpark_all(&self) {
+        self.park_slots.iter().for_each(|park_slot| {
+            park_slot.unpark();
+        });
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.is_shutdown.load(Ordering::Relaxed)
+    }
+
+    /// Add an actor, which the worker threads pick up in their next rounds.
+    pub fn register_actor(&self, actor: Actor) -> Result<ActorId> {
+        let id = self.actors.register(actor, self.parallelism)?;
+        // A parked thread with no actors may not wake up by itself
+        self.unpark_all();
+        Ok(id)
+    }
+
+    pub fn unregister_actor(&self, id: ActorId) -> Result<()> {
+        self.actors.unregister(id)
+    }
+
+    /// Run all actors and returns whether any of them has done some work.
+    pub fn run_actors(&self) -> bool {
+        self.actors.run_all()
+    }
+}
+
+/// Wakes up a thread that is parked in `block_on`.
+struct ParkerWaker(Box<dyn Parker>);
+
+impl ArcWake for ParkerWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+/// Wakes up a worker thread that is running tasks in `block_on`.
+struct WorkerWaker {
+    executor: Arc<Executor>,
+    thread_id: usize,
+    is_woken: AtomicBool,
+}
+
+impl ArcWake for WorkerWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.is_woken.store(true, Ordering::SeqCst);
+        arc_self.executor.park_slots[arc_self.thread_id].unpark();
+    }
+}
+
+/// The queue of the local tasks of an executor thread.
+///
+/// Only the owner thread enqueues tasks to and dequeues tasks from the local
+/// part, which requires no synchronization. The local tasks that are woken up
+/// by other threads are sent to the remote part, which is drained to the local
+/// part by the owner thread.
+struct LocalQueue {
+    local: UnsafeCell<VecDeque<Arc<Task>>>,
+    remote: (Sender<Arc<Task>>, Receiver<Arc<Task>>),
+}
+
+// Safety. The local part is only accessed by the owner thread.
+unsafe impl Sync for LocalQueue {}
+
+impl LocalQueue {
+    pub fn new() -> Self {
+        Self {
+            local: UnsafeCell::new(VecDeque::new()),
+            remote: flume::unbounded(),
+        }
+    }
+
+    /// Enqueue a task on the owner thread.
+    pub unsafe fn push(&self, task: Arc<Task>) {
+        (*self.local.get()).push_back(task);
+    }
+
+    /// Enqueue a task on any other thread.
+    pub fn push_remote(&self, task: Arc<Task>) {
+        self.remote.0.send(task).unwrap();
+    }
+
+    /// Dequeue a task on the owner thread.
+    pub unsafe fn pop(&self) -> Option<Arc<Task>> {
+        let local = &mut *self.local.get();
+        local.extend(self.remote.1.try_iter());
+        local.pop_front()
+    }
+
+    /// Returns whether other threads have enqueued any tasks, which is the only
+    /// part of the queue that the owner thread cannot know by itself when parked.
+    pub fn has_remote(&self) -> bool {
+        !self.remote.1.is_empty()
+    }
+}
+
+/// The run queue of an executor thread.
+///
+/// There is one level of queues per priority. At each level, the tasks that can
+/// run on any thread are kept in the shared queue, which may be stolen by idle
+/// sibling threads. The tasks that are restricted to a subset of threads are
+/// kept in the pinned queue, which only the owner thread dequeues from. This
+/// way, stealing never needs to skip over the tasks that it is not allowed to take.
+struct RunQueue {
+    levels: Vec<RunQueueLevel>,
+}
+
+struct RunQueueLevel {
+    shared: (Sender<Arc<Task>>, Receiver<Arc<Task>>),
+    pinned: (Sender<Arc<Task>>, Receiver<Arc<Task>>),
+}
+
+/// The priority to start dequeuing from at each tick, which repeats itself.
+///
+/// Out of every seven dequeues, four start from the high priority, two from
+/// the normal priority and one from the low priority. So the tasks of a lower
+/// priority still get their share of the thread when the higher ones are busy.
+const PRIORITY_SCHEDULE: [Priority; 7] = [
+    Priority::High,
+    Priority::Normal,
+    Priority::High,
+    Priority::Low,
+    Priority::High,
+    Priority::Normal,
+    Priority::High,
+];
+
+impl RunQueue {
+    /// Create a run queue that holds up to `capacity` tasks per priority.
+    pub fn new(capacity: usize) -> Self {
+        let levels = (0..Priority::COUNT)
+            .map(|_| RunQueueLevel {
+                shared: flume::bounded(capacity),
+                pinned: flume::bounded(capacity),
+            })
+            .collect();
+        Self { levels }
+    }
+
+    /// Enqueue a task, or give it back if the queue is full.
+    pub fn try_push(&self, task: Arc<Task>) -> core::result::Result<(), Arc<Task>> {
+        let level = &self.levels[task.sched_info().priority().index()];
+        let is_pinned = !task.sched_info().affinity().read().is_full();
+        let sender = if is_pinned {
+            &level.pinned.0
+        } else {
+            &level.shared.0
+        };
+        sender.try_send(task).map_err(|e| match e {
+            TrySendError::Full(task) | TrySendError::Disconnected(task) => task,
+        })
+    }
+
+    /// Dequeue a task for the owner thread.
+    ///
+    /// The tick decides which priority is visited first, after which the others
+    /// are visited from the highest to the lowest. It is also used to alternate
+    /// between the two queues of a level so that neither of them can starve the other.
+    pub fn pop(&self, tick: u32) -> Option<Arc<Task>> {
+        let first = PRIORITY_SCHEDULE[tick as usize % PRIORITY_SCHEDULE.len()];
+        core::iter::once(first)
+            .chain(Priority::ALL.iter().copied().filter(|p| *p != first))
+            .find_map(|priority| self.levels[priority.index()].pop(tick))
+    }
+
+    /// Dequeue a task for a sibling thread, preferring higher priorities.
+    pub fn steal(&self) -> Option<Arc<Task>> {
+        self.levels
+            .iter()
+            .find_map(|level| level.shared.1.try_recv().ok())
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|level| level.shared.1.len() + level.pinned.1.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels
+            .iter()
+            .all(|level| level.shared.1.is_empty() && level.pinned.1.is_empty())
+    }
+
+    pub fn has_stealable(&self) -> bool {
+        self.levels.iter().any(|level| !level.shared.1.is_empty())
+    }
+}
+
+impl RunQueueLevel {
+    fn pop(&self, tick: u32) -> Option<Arc<Task>> {
+        let (first, second) = if tick % 2 == 0 {
+            (&self.pinned.1, &self.shared.1)
+        } else {
+            (&self.shared.1, &self.pinned.1)
+        };
+        first.try_recv().or_else(|_| second.try_recv()).ok()
+    }
+}
@@ -0,0 +1,148 @@
+use core::time::Duration;
+
+use spin::Once;
+
+use crate::prelude::*;
+
+/// A mechanism to block an idle executor thread until there is new work.
+///
+/// Each executor thread owns a parker, which is used by the thread itself to
+/// park and by other threads to unpark it. A custom parker can be supplied via
+/// `runtime::Builder::parker`, e.g., one that blocks on an eventfd registered to
+/// an io_uring so that I/O completions can also wake up the thread.
+pub trait Parker: Send + Sync {
+    /// Block the current thread until it is unparked or the timeout expires.
+    ///
+    /// If `unpark` has been called since the last `park`, this method must
+    /// return immediately. Spurious wakeups are allowed.
+    fn park(&self, timeout: Option<Duration>);
+
+    /// Wake up the thread that is blocked in (or about to call) `park`.
+    fn unpark(&self);
+}
+
+/// A parker that busy-waits, which is the default without std.
+///
+/// An idle thread parked this way still occupies a CPU, and a timed park does
+/// not wait at all. So an enclave should supply a parker that sleeps in the
+/// host instead, e.g., on an eventfd through OCalls.
+pub struct SpinParker {
+    token: AtomicBool,
+}
+
+impl SpinParker {
+    pub fn new() -> Self {
+        let token = AtomicBool::new(false);
+        Self { token }
+    }
+}
+
+impl Parker for SpinParker {
+    fn park(&self, timeout: Option<Duration>) {
+        // Without a clock, a timed park can only be treated as a spurious wakeup
+        if timeout.is_some() {
+            return;
+        }
+
+        while !self.token.swap(false, Ordering::Acquire) {
+            core::sync::atomic::spin_loop_hint();
+        }
+    }
+
+    fn unpark(&self) {
+        self.token.store(true, Ordering::Release);
+    }
+}
+
+/// A parker that puts the thread to sleep with `std::thread::park`.
+#[cfg(any(test, feature = "std"))]
+pub struct ThreadParker {
+    thread: std::thread::Thread,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl ThreadParker {
+    /// Create a parker for the current thread.
+    pub fn new() -> Self {
+        let thread = std::thread::current();
+        Self { thread }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl Parker for ThreadParker {
+    fn park(&self, timeout: Option<Duration>) {
+        match timeout {
+            None => std::thread::park(),
+            Some(timeout) => std::thread::park_timeout(timeout),
+        }
+    }
+
+    fn unpark(&self) {
+        self.thread.unpark();
+    }
+}
+
+/// A function that creates a parker, which is called on the thread to be parked.
+pub(crate) type ParkerFactory = Arc<dyn Fn() -> Box<dyn Parker> + Send + Sync + 'static>;
+
+/// The parker used by `run_tasks` if none is given.
+#[cfg(any(test, feature = "std"))]
+pub(crate) fn default_parker() -> Box<dyn Parker> {
+    Box::new(ThreadParker::new())
+}
+
+#[cfg(not(any(test, feature = "std")))]
+pub(crate) fn default_parker() -> Box<dyn Parker> {
+    Box::new(SpinParker::new())
+}
+
+/// The parking state of an executor thread.
+pub(crate) struct ParkSlot {
+    is_parked: AtomicBool,
+    parker: Once<Box<dyn Parker>>,
+}
+
+impl ParkSlot {
+    pub fn new() -> Self {
+        let is_parked = AtomicBool::new(false);
+        let parker = Once::new();
+        Self { is_parked, parker }
+    }
+
+    /// Install the parker of the thread, which must be done before the first park.
+    pub fn init(&self, parker: Box<dyn Parker>) {
+        self.parker.call_once(|| parker);
+    }
+
+    /// Park the current thread unless `has_work` tells that there is new work.
+    ///
+    /// The thread is marked as parked before `has_work` is checked. Thus, any
+    /// work that arrives after the check is guaranteed to see the mark and
+    /// unpark the thread.
+    pub fn park(&self, timeout: Option<Duration>, has_work: impl FnOnce() -> bool) {
+        let parker = self.parker.get().expect("the parker must be initialized");
+
+        self.is_parked.store(true, Ordering::SeqCst);
+        if !has_work() {
+            parker.park(timeout);
+        }
+        self.is_parked.store(false, Ordering::SeqCst);
+    }
+
+    /// Unpark the thread if it is parked. Returns whether it was parked.
+    pub fn unpark(&self) -> bool {
+        if !self.is_parked.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+
+        // The parker is always initialized before the thread is marked as parked
+        let parker = self.parker.get().unwrap();
+        parker.unpark();
+        true
+    }
+
+    pub fn is_parked(&self) -> bool {
+        self.is_parked.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,102 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Weak;
+use core::sync::atomic::AtomicUsize;
+
+use crate::prelude::*;
+use crate::task::{Task, TaskId};
+
+/// The number of shards of a registry.
+const NUM_SHARDS: usize = 16;
+
+/// The live tasks of an executor, i.e., those that have been accepted but not
+/// torn down yet.
+///
+/// The tasks are sharded by their IDs, so that the threads that spawn and tear
+/// down tasks rarely contend on a lock. Counting the tasks, e.g., to drain an
+/// executor, takes no lock at all.
+pub(crate) struct TaskRegistry {
+    shards: Vec<Mutex<BTreeMap<TaskId, Weak<Task>>>>,
+    num_tasks: AtomicUsize,
+    // Whether new tasks are refused, which is the first step of shutdown
+    is_closed: AtomicBool,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        let shards = (0..NUM_SHARDS)
+            .map(|_| Mutex::new(BTreeMap::new()))
+            .collect();
+        Self {
+            shards,
+            num_tasks: AtomicUsize::new(0),
+            is_closed: AtomicBool::new(false),
+        }
+    }
+
+    fn shard(&self, tid: TaskId) -> &Mutex<BTreeMap<TaskId, Weak<Task>>> {
+        &self.shards[(tid.0 % NUM_SHARDS as u64) as usize]
+    }
+
+    /// Add a task unless the registry is closed.
+    pub fn register(&self, task: &Arc<Task>) -> bool {
+        let mut shard = self.shard(task.tid()).lock();
+        if self.is_closed() {
+            return false;
+        }
+        shard.insert(task.tid(), Arc::downgrade(task));
+        self.num_tasks.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    pub fn unregister(&self, tid: TaskId) {
+        let mut shard = self.shard(tid).lock();
+        if shard.remove(&tid).is_some() {
+            self.num_tasks.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Returns the task of the given ID if it has not been torn down yet.
+    pub fn find(&self, tid: TaskId) -> Option<Arc<Task>> {
+        self.shard(tid)
+            .lock()
+            .get(&tid)
+            .and_then(|task| task.upgrade())
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_tasks.load(Ordering::Acquire)
+    }
+
+    /// Refuse any new tasks from now on.
+    ///
+    /// Once this returns, every task that has been accepted is counted.
+    pub fn close(&self) {
+        self.is_closed.store(true, Ordering::Release);
+        // A task that is being added has either missed the flag, in which case
+        // it is added before the lock of its shard is released, or sees it
+        for shard in &self.shards {
+            drop(shard.lock());
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Ordering::Acquire)
+    }
+
+    /// Returns the live tasks, in the order of their IDs.
+    pub fn live_tasks(&self) -> Vec<Arc<Task>> {
+        let mut tasks: Vec<Arc<Task>> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock();
+                shard
+                    .values()
+                    .filter_map(|task| task.upgrade())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        tasks.sort_by_key(|task| task.tid());
+        tasks
+    }
+}
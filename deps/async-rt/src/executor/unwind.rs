@@ -0,0 +1,86 @@
+//! Panic isolation for the tasks run by the executor.
+//!
+//! Unwinding is caught with std by default. Without std, the embedder can
+//! provide an `Unwinder` through `Builder::unwinder`, e.g., one that uses
+//! `sgx_tstd::panic::catch_unwind` in an enclave. Without either, a panic in a
+//! task still takes down its executor thread.
+
+use alloc::sync::Arc;
+use core::cell::Cell;
+
+/// A way to catch the panics that unwind out of tasks and blocking jobs.
+pub trait Unwinder: Send + Sync {
+    /// Run the closure and catch the panic that unwinds out of it. Returns
+    /// false if the closure panicked.
+    fn catch_unwind(&self, f: &mut dyn FnMut()) -> bool;
+
+    /// Returns whether the current thread is unwinding because of a panic.
+    fn panicking(&self) -> bool;
+}
+
+// The unwinder of the runtime that the current thread belongs to, if any
+#[thread_local]
+static UNWINDER: Cell<Option<*const dyn Unwinder>> = Cell::new(None);
+
+/// Use the unwinder for the panics on the current thread while running the closure.
+pub(crate) fn with_unwinder<R>(unwinder: Option<&Arc<dyn Unwinder>>, f: impl FnOnce() -> R) -> R {
+    struct Reset(Option<*const dyn Unwinder>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            UNWINDER.set(self.0);
+        }
+    }
+
+    let _reset = Reset(UNWINDER.replace(unwinder.map(|unwinder| &**unwinder as *const _)));
+    f()
+}
+
+fn current_unwinder() -> Option<&'static dyn Unwinder> {
+    // Safety. The unwinder outlives the closure given to `with_unwinder`.
+    UNWINDER.get().map(|unwinder| unsafe { &*unwinder })
+}
+
+/// Run a closure and catch the panic that unwinds out of it, if possible.
+pub(crate) fn catch_unwind<R>(f: impl FnOnce() -> R) -> Result<R, ()> {
+    let unwinder = match current_unwinder() {
+        Some(unwinder) => unwinder,
+        None => return default_catch_unwind(f),
+    };
+
+    let mut f = Some(f);
+    let mut output = None;
+    let is_ok = unwinder.catch_unwind(&mut || output = f.take().map(|f| f()));
+    match output {
+        Some(output) if is_ok => Ok(output),
+        _ => Err(()),
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+fn default_catch_unwind<R>(f: impl FnOnce() -> R) -> Result<R, ()> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|_| ())
+}
+
+#[cfg(not(any(test, feature = "std")))]
+fn default_catch_unwind<R>(f: impl FnOnce() -> R) -> Result<R, ()> {
+    Ok(f())
+}
+
+/// Returns whether the current thread is unwinding because of a panic.
+pub(crate) fn panicking() -> bool {
+    match current_unwinder() {
+        Some(unwinder) => unwinder.panicking(),
+        None => default_panicking(),
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+fn default_panicking() -> bool {
+    std::thread::panicking()
+}
+
+#[cfg(not(any(test, feature = "std")))]
+fn default_panicking() -> bool {
+    false
+}
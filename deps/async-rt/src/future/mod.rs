@@ -0,0 +1,47 @@
+//! Future combinators that work without std.
+//!
+//! Besides the functions here, the crate provides the `join!`, `try_join!`
+//! and `select!` macros. All of them poll their futures in a rotating order,
+//! which starts from a different future at each invocation, so that no future is
+//! always favored over the others, even in a loop.
+//!
+//! `timeout` is the one from `time`, so it needs a clock like any timer. Without
+//! std, e.g., in an SGX enclave, a clock must be installed with `time::set_clock`
+//! first, as the enclave example does with its OCall-based clock.
+
+pub use self::race::{race, Race};
+pub use crate::time::{timeout, Timeout};
+
+mod race;
+
+// The items used by the macros
+#[doc(hidden)]
+pub mod support {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    pub use core::future::Future;
+    pub use core::pin::Pin;
+    pub use core::task::Poll;
+    pub use futures::future::{maybe_done, poll_fn};
+
+    /// Returns the index of the future to poll first among `count` ones, which
+    /// differs from one invocation of a macro to the next.
+    ///
+    /// A deterministic runtime draws it from its RNG, so that a run can be replayed.
+    pub fn start_index(count: usize) -> usize {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let is_simulated =
+            crate::executor::current().map_or(false, |executor| executor.simulation().is_some());
+        if is_simulated {
+            crate::sim::random_index(count)
+        } else {
+            NEXT.fetch_add(1, Ordering::Relaxed) % count
+        }
+    }
+
+    /// Returns an empty slot for the output of a future.
+    pub fn output_slot<F: Future>(_future: &F) -> Option<F::Output> {
+        None
+    }
+}
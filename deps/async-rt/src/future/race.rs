@@ -0,0 +1,61 @@
+use crate::prelude::*;
+
+/// Await two futures and return the output of the one that completes first.
+///
+/// The other future is dropped. If both are ready, either may win, as the
+/// two futures take turns to be polled first.
+///
+/// ```ignore
+/// let res = future::race(sock.read(buf), async {
+///     shutdown.notified().await;
+///     Err(Errno::ECANCELED)
+/// })
+/// .await;
+/// ```
+pub fn race<A, B>(a: A, b: B) -> Race<A, B>
+where
+    A: Future,
+    B: Future<Output = A::Output>,
+{
+    Race {
+        a,
+        b,
+        is_b_first: false,
+    }
+}
+
+/// A future that races two futures. See `race`.
+pub struct Race<A, B> {
+    a: A,
+    b: B,
+    is_b_first: bool,
+}
+
+impl<A, B> Future for Race<A, B>
+where
+    A: Future,
+    B: Future<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety. The futures are never moved out of the pinned self.
+        let self_ = unsafe { self.get_unchecked_mut() };
+        let a = unsafe { Pin::new_unchecked(&mut self_.a) };
+        let b = unsafe { Pin::new_unchecked(&mut self_.b) };
+        let is_b_first = self_.is_b_first;
+        self_.is_b_first = !is_b_first;
+
+        if is_b_first {
+            if let Poll::Ready(output) = b.poll(cx) {
+                return Poll::Ready(output);
+            }
+            a.poll(cx)
+        } else {
+            if let Poll::Ready(output) = a.poll(cx) {
+                return Poll::Ready(output);
+            }
+            b.poll(cx)
+        }
+    }
+}
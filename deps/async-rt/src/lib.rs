@@ -1,4 +1,4 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![feature(const_fn)]
 #![feature(thread_local)]
 
@@ -12,10 +12,17 @@ extern crate flume;
 extern crate spin;
 
 pub mod executor;
+pub mod future;
 mod macros;
 pub mod prelude;
+pub mod runtime;
 pub mod sched;
+pub mod sim;
+pub mod sync;
 pub mod task;
+pub mod time;
+
+pub use self::runtime::{Builder, Handle, Runtime};
 
 // All unit tests
 #[cfg(test)]
@@ -57,6 +64,44 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_task_local_scope_and_inherit() {
+        task_local! {
+            static REQUEST_ID: u64 = 0;
+        }
+
+        assert!(REQUEST_ID.try_with(|id| *id).is_err());
+        // Outside of any task, a scope runs its future without the value
+        let scoped = REQUEST_ID.scope(42, async { REQUEST_ID.try_with(|id| *id).is_err() });
+        assert!(scoped.now_or_never() == Some(true));
+
+        crate::test_rt::run_blocking(async {
+            assert!(REQUEST_ID.try_with(|id| *id) == Ok(0));
+
+            // A scoped value is only seen by the future in the scope
+            let scoped = REQUEST_ID.scope(42, async {
+                let id = REQUEST_ID.with(|id| *id);
+                crate::sched::yield_().await;
+                (id, REQUEST_ID.with(|id| *id))
+            });
+            assert!(scoped.await == (42, 42));
+            assert!(REQUEST_ID.with(|id| *id) == 0);
+
+            // A child task inherits the value in the scope of its parent
+            let join_handle = REQUEST_ID
+                .scope(7, async {
+                    crate::task::Builder::new()
+                        .inherit(&REQUEST_ID)
+                        .spawn(async { REQUEST_ID.with(|id| *id) })
+                        .unwrap()
+                })
+                .await;
+            assert!(join_handle.await == Ok(7));
+            let not_inherited = crate::task::spawn(async { REQUEST_ID.with(|id| *id) });
+            assert!(not_inherited.await == Ok(0));
+        });
+    }
+
     #[test]
     fn test_spawn_and_join() {
         crate::test_rt::run_blocking(async {
@@ -71,7 +116,137 @@ mod tests {
                 .collect();
 
             for (i, join_handle) in join_handles.iter_mut().enumerate() {
-                assert!(join_handle.await == (i as i32));
+                assert!(join_handle.await == Ok(i as i32));
+            }
+        });
+    }
+
+    #[test]
+    fn test_dedup_wakeups() {
+        crate::test_rt::run_blocking(async {
+            use std::sync::atomic::AtomicUsize;
+
+            // Wake up the task many times during its first poll and never again
+            let num_polls = Arc::new(AtomicUsize::new(0));
+            let join_handle = {
+                let num_polls = num_polls.clone();
+                crate::task::spawn(futures::future::poll_fn(move |cx| {
+                    if num_polls.fetch_add(1, Ordering::AcqRel) == 0 {
+                        for _ in 0..10 {
+                            cx.waker().wake_by_ref();
+                        }
+                    }
+                    Poll::<()>::Pending
+                }))
+            };
+            while num_polls.load(Ordering::Acquire) < 2 {
+                crate::sched::yield_().await;
+            }
+            for _ in 0..100 {
+                crate::sched::yield_().await;
+            }
+
+            // The wakeups during the poll re-queue the task exactly once
+            assert!(num_polls.load(Ordering::Acquire) == 2);
+            join_handle.abort();
+            assert!(join_handle.await == Err(crate::task::JoinError::Cancelled));
+        });
+    }
+
+    #[test]
+    fn test_combinators() {
+        crate::test_rt::run_blocking(async {
+            use futures::future::pending;
+
+            let yield_then = |value: u32| async move {
+                crate::sched::yield_().await;
+                value
+            };
+            assert!(join!(yield_then(1), async { "two" }, yield_then(3)) == (1, "two", 3));
+
+            let ok = |value: u32| async move { Ok::<u32, &str>(value) };
+            assert!(try_join!(ok(1), yield_then(2).map(Ok)) == Ok((1, 2)));
+            let res = try_join!(ok(1), pending::<Result<u32>>(), async { Err("failed") });
+            assert!(res == Err("failed"));
+
+            // The handler of the first future to complete is run
+            let res: Result<u32> = async {
+                let value = select! {
+                    value = yield_then(1) => value + 1,
+                    _ = pending::<()>() => return Err("pending"),
+                };
+                Ok(value)
+            }
+            .await;
+            assert!(res == Ok(2));
+
+            // Of the futures that are always ready, each wins some of the time
+            let mut num_wins = [0; 3];
+            for _ in 0..30 {
+                let winner = select! {
+                    _ = async {} => 0,
+                    _ = async {} => 1,
+                    _ = async {} => { 2 }
+                };
+                num_wins[winner] += 1;
+            }
+            assert!(num_wins.iter().all(|num_wins| *num_wins > 0));
+
+            assert!(crate::future::race(pending(), yield_then(7)).await == 7);
+        });
+    }
+
+    #[test]
+    fn test_join_set() {
+        crate::test_rt::run_blocking(async {
+            use crate::task::JoinSet;
+
+            let mut join_set = JoinSet::new();
+            for i in 0..10 {
+                join_set.spawn(async move {
+                    crate::sched::yield_().await;
+                    i
+                });
+            }
+            assert!(join_set.len() == 10);
+            let mut outputs = Vec::new();
+            while let Some(output) = join_set.join_next().await {
+                outputs.push(output.unwrap());
+            }
+            outputs.sort();
+            assert!(outputs == (0..10).collect::<Vec<i32>>());
+            assert!(join_set.is_empty());
+
+            // Shutting down a set aborts the remaining tasks
+            join_set.spawn(futures::future::pending());
+            join_set.shutdown().await;
+            assert!(join_set.is_empty());
+
+            // So does dropping it
+            let is_started = Arc::new(AtomicBool::new(false));
+            let is_dropped = Arc::new(AtomicBool::new(false));
+            let mut join_set = JoinSet::new();
+            {
+                let is_started = is_started.clone();
+                let is_dropped = is_dropped.clone();
+                join_set.spawn(async move {
+                    struct Guard(Arc<AtomicBool>);
+                    impl Drop for Guard {
+                        fn drop(&mut self) {
+                            self.0.store(true, Ordering::Release);
+                        }
+                    }
+                    let _guard = Guard(is_dropped);
+                    is_started.store(true, Ordering::Release);
+                    futures::future::pending::<()>().await;
+                });
+            }
+            while !is_started.load(Ordering::Acquire) {
+                crate::sched::yield_().await;
+            }
+            drop(join_set);
+            while !is_dropped.load(Ordering::Acquire) {
+                crate::sched::yield_().await;
             }
         });
     }
@@ -98,220 +273,1412 @@ mod tests {
             crate::sched::yield_().await;
 
             assert!(*current.sched_info().affinity().read() == new_affinity);
+
+            // An affinity without any worker threads does not stop the task
+            *current.sched_info().affinity().write() = Affinity::new_empty();
+            for _ in 0..10 {
+                crate::sched::yield_().await;
+            }
+            *current.sched_info().affinity().write() = Affinity::full(64);
+            crate::sched::yield_().await;
         });
     }
-}
 
-// Test runtime
-#[cfg(test)]
-mod test_rt {
-    use crate::prelude::*;
+    #[test]
+    fn test_set_affinity() {
+        crate::test_rt::run_blocking(async {
+            use crate::sched::{current_thread_id, Affinity};
 
-    pub(crate) fn run_blocking<T: Send + 'static>(
-        future: impl Future<Output = T> + 'static + Send,
-    ) -> T {
-        TEST_RT.run_blocking(future)
+            let only = |thread_id: usize| {
+                let mut affinity = Affinity::new_empty();
+                affinity.set(thread_id, true);
+                affinity
+            };
+
+            // Invalid affinities are rejected
+            let empty = Affinity::new_empty();
+            assert!(crate::sched::set_affinity(&empty).await.is_err());
+            let too_large = Affinity::full(Affinity::max_threads() + 1);
+            assert!(crate::sched::set_affinity(&too_large).await.is_err());
+            let current = crate::task::current();
+            assert!(current.sched_info().affinity().read().is_full());
+
+            // The current task is migrated before the function returns
+            crate::sched::set_affinity(&only(2)).await.unwrap();
+            assert!(current_thread_id() == Some(2));
+            for _ in 0..10 {
+                crate::sched::yield_().await;
+                assert!(current_thread_id() == Some(2));
+            }
+
+            // Change the affinity of another task
+            let is_moved = Arc::new(AtomicBool::new(false));
+            let join_handle = {
+                let is_moved = is_moved.clone();
+                crate::task::spawn(async move {
+                    while !is_moved.load(Ordering::Acquire) {
+                        crate::sched::yield_().await;
+                    }
+                    crate::sched::yield_().await;
+                    current_thread_id().unwrap()
+                })
+            };
+            let tid = join_handle.task_id();
+            crate::sched::set_task_affinity(tid, &only(1)).unwrap();
+            is_moved.store(true, Ordering::Release);
+            assert!(join_handle.await == Ok(1));
+            assert!(crate::sched::set_task_affinity(tid, &only(1)).is_err());
+        });
     }
 
-    const TEST_PARALLELISM: u32 = 3;
+    #[test]
+    fn test_task_builder() {
+        crate::test_rt::run_blocking(async {
+            use crate::sched::{current_thread_id, Affinity, Priority};
+            use crate::task::Builder;
 
-    lazy_static! {
-        static ref TEST_RT: TestRt = TestRt::new(TEST_PARALLELISM);
+            let mut affinity = Affinity::new_empty();
+            affinity.set(1, true);
+            let join_handle = Builder::new()
+                .name("conn-42")
+                .affinity(affinity)
+                .priority(Priority::High)
+                .spawn(async {
+                    let current = crate::task::current();
+                    assert!(current.name() == Some("conn-42"));
+                    assert!(format!("{:?}", current).contains("conn-42"));
+                    assert!(crate::sched::priority() == Priority::High);
+                    current_thread_id().unwrap()
+                })
+                .unwrap();
+            assert!(join_handle.await == Ok(1));
+
+            // An invalid affinity is rejected
+            let empty = Affinity::new_empty();
+            assert!(Builder::new().affinity(empty).spawn(async {}).is_err());
+
+            // Unnamed tasks have no names
+            let unnamed = Builder::new().spawn(async { crate::task::current().name().is_none() });
+            assert!(unnamed.unwrap().await == Ok(true));
+        });
     }
 
-    struct TestRt {
-        threads: Vec<std::thread::JoinHandle<()>>,
+    #[test]
+    fn test_work_stealing() {
+        crate::test_rt::run_blocking(async {
+            use crate::sched::Affinity;
+            use std::sync::atomic::AtomicUsize;
+            use std::time::{Duration, Instant};
+
+            const NUM_TASKS: usize = 30;
+            let hog_started = Arc::new(AtomicBool::new(false));
+            let counter = Arc::new(AtomicUsize::new(0));
+
+            // Occupy thread 0 with a task that never yields until all other tasks
+            // have done their work. Some of these tasks are enqueued to thread 0,
+            // so they can only make progress by being stolen.
+            let hog = {
+                let hog_started = hog_started.clone();
+                let counter = counter.clone();
+                crate::task::spawn(async move {
+                    let current = crate::task::current();
+                    *current.sched_info().affinity().write() = {
+                        let mut affinity = Affinity::new_empty();
+                        affinity.set(0, true);
+                        affinity
+                    };
+                    crate::sched::yield_().await;
+                    hog_started.store(true, Ordering::Release);
+
+                    let deadline = Instant::now() + Duration::from_secs(5);
+                    while counter.load(Ordering::Acquire) < NUM_TASKS && Instant::now() < deadline {
+                        core::sync::atomic::spin_loop_hint();
+                    }
+                    counter.load(Ordering::Acquire)
+                })
+            };
+            while !hog_started.load(Ordering::Acquire) {
+                crate::sched::yield_().await;
+            }
+
+            for _ in 0..NUM_TASKS {
+                let counter = counter.clone();
+                crate::task::spawn(async move {
+                    counter.fetch_add(1, Ordering::Release);
+                });
+            }
+
+            assert!(hog.await == Ok(NUM_TASKS));
+        });
     }
 
-    impl TestRt {
-        pub fn new(parallelism: u32) -> Self {
-            crate::executor::set_parallelism(parallelism).unwrap();
-            crate::test_logger::init().unwrap();
+    #[test]
+    fn test_priority() {
+        use crate::runtime::Builder;
+        use crate::sched::Priority;
 
-            let threads = (0..parallelism)
-                .map(|_| std::thread::spawn(|| crate::executor::run_tasks()))
-                .collect::<Vec<_>>();
-            Self { threads }
+        // A single worker thread runs the tasks in the order of their dequeuing
+        let runtime = Builder::new().worker_threads(1).build().unwrap();
+        let order = runtime.block_on(async {
+            assert!(crate::sched::priority() == Priority::Normal);
+            crate::sched::set_priority(Priority::High);
+            assert!(crate::sched::priority() == Priority::High);
+
+            const NUM_TASKS: usize = 20;
+            let order = Arc::new(Mutex::new(Vec::new()));
+            let join_handles: Vec<_> = [Priority::Low, Priority::High]
+                .iter()
+                .flat_map(|priority| (0..NUM_TASKS).map(move |_| *priority))
+                .map(|priority| {
+                    let order = order.clone();
+                    crate::task::spawn_with_priority(priority, async move {
+                        order.lock().push(crate::sched::priority());
+                    })
+                })
+                .collect();
+            for join_handle in join_handles {
+                join_handle.await.unwrap();
+            }
+            let order = order.lock().clone();
+            order
+        });
+
+        // The high-priority tasks run first, though not to the exclusion of the low ones
+        let first_half = &order[..order.len() / 2];
+        let num_high = first_half.iter().filter(|p| **p == Priority::High).count();
+        assert!(num_high > first_half.len() * 3 / 4);
+        assert!(num_high < first_half.len());
+    }
+
+    #[test]
+    fn test_coop_budget() {
+        use crate::runtime::Builder;
+        use crate::sched::coop;
+
+        // With a single worker thread, the flag can only be set if the hog yields
+        let runtime = Builder::new().worker_threads(1).build().unwrap();
+        runtime.block_on(async {
+            let is_done = Arc::new(AtomicBool::new(false));
+            let hog = {
+                let is_done = is_done.clone();
+                crate::task::spawn(async move {
+                    let mut num_ops: u32 = 0;
+                    while !is_done.load(Ordering::Acquire) {
+                        crate::sched::consume_budget().await;
+                        num_ops += 1;
+                    }
+                    num_ops
+                })
+            };
+            crate::task::spawn(async move {
+                is_done.store(true, Ordering::Release);
+            })
+            .await
+            .unwrap();
+            assert!(hog.await.unwrap() >= coop::INITIAL_BUDGET);
+        });
+
+        // Outside a task, the budget is unlimited
+        assert!(!coop::is_budget_exhausted());
+    }
+
+    #[test]
+    fn test_spawn_local() {
+        crate::test_rt::run_blocking(async {
+            use crate::sched::{current_thread_id, Affinity};
+            use crate::sync::oneshot;
+            use std::cell::Cell;
+            use std::rc::Rc;
+
+            let (sender, receiver) = oneshot::channel();
+            let thread_id = current_thread_id().unwrap();
+            let join_handle = crate::task::spawn_local(async move {
+                // The state shared by local tasks needs not be `Send`
+                let counter = Rc::new(Cell::new(0));
+                let join_handles: Vec<_> = (0..10)
+                    .map(|_| {
+                        let counter = counter.clone();
+                        crate::task::spawn_local(async move {
+                            crate::sched::yield_().await;
+                            counter.set(counter.get() + 1);
+                            current_thread_id().unwrap()
+                        })
+                    })
+                    .collect();
+                for join_handle in join_handles {
+                    assert!(join_handle.await == Ok(thread_id));
+                }
+
+                // A local task can be woken up by other threads
+                receiver.await.unwrap();
+                assert!(crate::sched::set_affinity(&Affinity::new_full())
+                    .await
+                    .is_err());
+                (counter.get(), current_thread_id().unwrap())
+            });
+
+            crate::task::spawn(async move {
+                let mut affinity = Affinity::new_empty();
+                affinity.set((thread_id + 1) % Affinity::max_threads(), true);
+                crate::sched::set_affinity(&affinity).await.unwrap();
+                sender.send(()).unwrap();
+            });
+            assert!(join_handle.await == Ok((10, thread_id)));
+        });
+    }
+
+    #[test]
+    fn test_local_tasks_on_shutdown() {
+        use crate::runtime::Builder;
+        use crate::sched::Affinity;
+        use crate::sync::oneshot;
+        use std::rc::Rc;
+        use std::sync::atomic::AtomicUsize;
+        use std::time::{Duration, Instant};
+
+        static NUM_STARTED: AtomicUsize = AtomicUsize::new(0);
+        static NUM_DROPPED: AtomicUsize = AtomicUsize::new(0);
+        static IS_BLOCKING: AtomicBool = AtomicBool::new(false);
+
+        struct Guard;
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                assert!(std::thread::current().name() == Some("local-rt-1"));
+                NUM_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
-        pub fn run_blocking<T: Send + 'static>(
-            &self,
-            future: impl Future<Output = T> + 'static + Send,
-        ) -> T {
-            crate::task::block_on(future)
+        let on_thread = |thread_id: usize| {
+            let mut affinity = Affinity::empty(2);
+            affinity.set(thread_id, true);
+            crate::task::Builder::new().affinity(affinity)
+        };
+
+        // A runtime dropped from one of its tasks does not wait for the local
+        // tasks of the other thread. The thread is kept busy meanwhile, so the
+        // local tasks are left in its queue, but they are still dropped by the
+        // thread before it exits
+        const NUM_TASKS: usize = 10;
+        let runtime = Builder::new()
+            .worker_threads(2)
+            .thread_name("local-rt")
+            .build()
+            .unwrap();
+        let handle = runtime.handle().clone();
+        // The local tasks are kept alive by the wakers in the channels
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..NUM_TASKS).map(|_| oneshot::channel::<()>()).unzip();
+        on_thread(1)
+            .spawn_on(&handle, async {
+                for receiver in receivers {
+                    crate::task::spawn_local(async {
+                        let _guard = Rc::new(Guard);
+                        NUM_STARTED.fetch_add(1, Ordering::Relaxed);
+                        let _ = receiver.await;
+                    });
+                }
+                while NUM_STARTED.load(Ordering::Relaxed) < NUM_TASKS {
+                    crate::sched::yield_().await;
+                }
+                IS_BLOCKING.store(true, Ordering::Relaxed);
+                std::thread::sleep(Duration::from_millis(100));
+            })
+            .unwrap();
+        on_thread(0)
+            .spawn_on(&handle, async move {
+                while !IS_BLOCKING.load(Ordering::Relaxed) {
+                    crate::sched::yield_().await;
+                }
+                drop(runtime);
+            })
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while handle.metrics().num_tasks > 0 {
+            assert!(Instant::now() < deadline);
+            std::thread::yield_now();
         }
+        drop(handle);
+        assert!(NUM_DROPPED.load(Ordering::Relaxed) == NUM_TASKS);
+        drop(senders);
     }
 
-    impl Drop for TestRt {
-        fn drop(&mut self) {
-            // Shutdown the executor and free the threads
-            crate::executor::shutdown();
+    #[test]
+    fn test_join_across_shutdown() {
+        use crate::runtime::Builder;
+        use crate::sync::oneshot;
+        use crate::task::JoinError;
+        use std::time::{Duration, Instant};
 
-            for th in self.threads.drain(0..self.threads.len()) {
-                th.join().unwrap();
+        // A runtime dropped from one of its tasks does not wait for the others,
+        // which are joined with the shutdown error
+        let runtime = Builder::new().worker_threads(1).build().unwrap();
+        let handle = runtime.handle().clone();
+        let (sender, receiver) = oneshot::channel::<()>();
+        let is_started = Arc::new(AtomicBool::new(false));
+        let join_handle = {
+            let is_started = is_started.clone();
+            handle.spawn(async move {
+                is_started.store(true, Ordering::Relaxed);
+                let _ = receiver.await;
+            })
+        };
+        handle.spawn(async move {
+            while !is_started.load(Ordering::Relaxed) {
+                crate::sched::yield_().await;
             }
+            drop(runtime);
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while handle.metrics().num_tasks > 0 {
+            assert!(Instant::now() < deadline);
+            std::thread::yield_now();
         }
+        assert!(join_handle.now_or_never() == Some(Err(JoinError::Shutdown)));
+        drop(sender);
     }
-}
 
-#[cfg(test)]
-mod test_logger {
-    use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+    #[test]
+    fn test_spawn_blocking() {
+        use crate::runtime::Builder;
+        use crate::task::JoinError;
+        use std::time::Duration;
 
-    static LOGGER: SimpleLogger = SimpleLogger;
+        let runtime = Builder::new()
+            .worker_threads(1)
+            .max_blocking_threads(2)
+            .thread_name("blocking-rt")
+            .build()
+            .unwrap();
+        let handle = runtime.handle().clone();
+        runtime.block_on(async {
+            // The only worker thread keeps running tasks while the jobs block
+            let is_done = Arc::new(AtomicBool::new(false));
+            let join_handles: Vec<_> = (0..4)
+                .map(|i| {
+                    let is_done = is_done.clone();
+                    crate::task::spawn_blocking(move || {
+                        while !is_done.load(Ordering::Acquire) {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                        let thread = std::thread::current();
+                        (i, thread.name().unwrap().to_string())
+                    })
+                })
+                .collect();
+            crate::task::spawn(async move {
+                is_done.store(true, Ordering::Release);
+            })
+            .await
+            .unwrap();
+            for (i, join_handle) in join_handles.into_iter().enumerate() {
+                let (output, thread_name) = join_handle.await.unwrap();
+                assert!(output == i);
+                assert!(thread_name.starts_with("blocking-rt-blocking-"));
+            }
 
-    pub fn init() -> std::result::Result<(), SetLoggerError> {
-        log::set_logger(&LOGGER).map(|()| log::set_max_level(LevelFilter::Info))
+            // A panic is confined to its job
+            let join_handle = crate::task::spawn_blocking(|| panic!("expected by the test"));
+            assert!(join_handle.await == Err(JoinError::Panicked));
+            assert!(crate::task::spawn_blocking(|| 1).await == Ok(1));
+
+            // A job that is aborted before any thread is free to run it is never run
+            let is_done = Arc::new(AtomicBool::new(false));
+            let is_run = Arc::new(AtomicBool::new(false));
+            let busy_handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let is_done = is_done.clone();
+                    crate::task::spawn_blocking(move || {
+                        while !is_done.load(Ordering::Acquire) {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                    })
+                })
+                .collect();
+            let join_handle = {
+                let is_run = is_run.clone();
+                crate::task::spawn_blocking(move || is_run.store(true, Ordering::Relaxed))
+            };
+            join_handle.abort();
+            is_done.store(true, Ordering::Release);
+            assert!(join_handle.await == Err(JoinError::Cancelled));
+            for busy_handle in busy_handles {
+                assert!(busy_handle.await == Ok(()));
+            }
+            assert!(!is_run.load(Ordering::Relaxed));
+        });
+
+        // A blocking job is refused after shutdown
+        drop(runtime);
+        let join_handle = handle.spawn_blocking(|| 1);
+        assert!(join_handle.is_finished());
     }
 
-    struct SimpleLogger;
+    #[test]
+    fn test_overflow() {
+        crate::test_rt::run_blocking(async {
+            // Much more than the total capacity of the run queues
+            const NUM_TASKS: usize = 10_000;
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|i| crate::task::spawn(async move { i }))
+                .collect();
+            for (i, join_handle) in join_handles.into_iter().enumerate() {
+                assert!(join_handle.await == Ok(i));
+            }
+        });
+    }
 
-    impl log::Log for SimpleLogger {
-        fn enabled(&self, metadata: &Metadata) -> bool {
-            metadata.level() <= Level::Info
-        }
+    #[test]
+    fn test_try_spawn() {
+        crate::test_rt::run_blocking(async {
+            let join_handle = crate::task::try_spawn(async { 42 }).unwrap();
+            assert!(join_handle.await == Ok(42));
+        });
 
-        fn log(&self, record: &Record) {
-            if self.enabled(record.metadata()) {
-                println!("[{}] {}", record.level(), record.args());
+        // Block the only worker thread so that its run queue fills up
+        let runtime = crate::runtime::Builder::new()
+            .max_queued_tasks(1)
+            .build()
+            .unwrap();
+        let handle = runtime.handle();
+        let is_blocked = Arc::new(AtomicBool::new(false));
+        let is_released = Arc::new(AtomicBool::new(false));
+        let (is_blocked_, is_released_) = (is_blocked.clone(), is_released.clone());
+        let blocker = handle.spawn(async move {
+            is_blocked_.store(true, Ordering::Release);
+            while !is_released_.load(Ordering::Acquire) {
+                core::sync::atomic::spin_loop_hint();
             }
+        });
+        while !is_blocked.load(Ordering::Acquire) {
+            std::thread::yield_now();
         }
 
-        fn flush(&self) {}
+        let first = handle.try_spawn(async { 1 }).unwrap();
+        assert!(handle.try_spawn(async { 2 }).err() == Some("too many tasks enqueued"));
+        is_released.store(true, Ordering::Release);
+        assert!(runtime.block_on(blocker) == Ok(()));
+        assert!(runtime.block_on(first) == Ok(1));
     }
-}
 
-/*
+    #[test]
+    fn test_abort() {
+        crate::test_rt::run_blocking(async {
+            use crate::task::JoinError;
+            use core::time::Duration;
 
-lazy_static! {
-    static ref FUTEX_TABLE: FutexTable = FutexTable::new();
-}
+            let join_handle = crate::task::spawn(async {
+                crate::time::sleep(Duration::from_secs(60)).await;
+            });
+            crate::sched::yield_().await;
+            assert!(!join_handle.is_finished());
 
-struct FutexTable {
-    table: Mutex<HashMap<usize, Vec<Waker>>>,
-}
+            join_handle.abort();
+            assert!(join_handle.await == Err(JoinError::Cancelled));
+
+            let join_handle = crate::task::spawn(async { 1 });
+            while !join_handle.is_finished() {
+                crate::sched::yield_().await;
+            }
+            join_handle.abort();
+            assert!(join_handle.await == Ok(1));
+        });
+    }
+
+    #[test]
+    fn test_panic() {
+        use std::cell::Cell;
+        use std::sync::atomic::AtomicUsize;
+
+        static NUM_DROPPED: AtomicUsize = AtomicUsize::new(0);
 
-impl FutexTable {
-    pub fn new() -> Self {
-        Self {
-            table: Mutex::new(HashMap::new()),
+        struct Guard(Cell<u32>);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                NUM_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
         }
+
+        task_local! {
+            static GUARD: Guard = Guard(Cell::new(0));
+        }
+
+        crate::test_rt::run_blocking(async {
+            use crate::task::JoinError;
+
+            // Panic on every thread at least once
+            const NUM_TASKS: usize = 10;
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|_| {
+                    crate::task::spawn(async {
+                        GUARD.with(|guard| guard.0.set(1));
+                        crate::sched::yield_().await;
+                        panic!("a panic that is expected by the test");
+                    })
+                })
+                .collect();
+            for join_handle in join_handles {
+                assert!(join_handle.await == Err(JoinError::Panicked));
+            }
+            // The task-locals are dropped shortly after the tasks are joined
+            while NUM_DROPPED.load(Ordering::Relaxed) < NUM_TASKS {
+                crate::sched::yield_().await;
+            }
+
+            // The executor threads keep serving other tasks
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|i| crate::task::spawn(async move { i }))
+                .collect();
+            for (i, join_handle) in join_handles.into_iter().enumerate() {
+                assert!(join_handle.await == Ok(i));
+            }
+        });
     }
 
-    pub async fn wait(&self, addr: &AtomicI32, expected_val: i32) -> () {
-        {
-            let table = self.table.lock().unwrap();
-            if addr.load(Ordering::Acquire) != expected_val {
-                return;
+    #[test]
+    fn test_unwinder() {
+        use crate::executor::Unwinder;
+        use crate::runtime::Builder;
+        use crate::task::JoinError;
+        use std::sync::atomic::AtomicUsize;
+        use std::time::Duration;
+
+        static NUM_CAUGHT: AtomicUsize = AtomicUsize::new(0);
+
+        // An unwinder as an embedder without std would provide
+        struct CountingUnwinder;
+
+        impl Unwinder for CountingUnwinder {
+            fn catch_unwind(&self, f: &mut dyn FnMut()) -> bool {
+                let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+                if res.is_err() {
+                    NUM_CAUGHT.fetch_add(1, Ordering::Relaxed);
+                }
+                res.is_ok()
+            }
+
+            fn panicking(&self) -> bool {
+                std::thread::panicking()
+            }
+        }
+
+        let rt = Builder::new().unwinder(CountingUnwinder).build().unwrap();
+        rt.block_on(async {
+            let join_handle = crate::task::spawn(async {
+                panic!("a panic that is expected by the test");
+            });
+            assert!(join_handle.await == Err(JoinError::Panicked));
+
+            let join_handle = crate::task::spawn_blocking(|| {
+                panic!("a panic that is expected by the test");
+            });
+            assert!(join_handle.await == Err(JoinError::Panicked));
+        });
+        assert!(NUM_CAUGHT.load(Ordering::Relaxed) == 2);
+
+        // An unwinder that cannot catch panics, as without any unwinding support
+        struct NoUnwinder;
+
+        impl Unwinder for NoUnwinder {
+            fn catch_unwind(&self, f: &mut dyn FnMut()) -> bool {
+                f();
+                true
+            }
+
+            fn panicking(&self) -> bool {
+                std::thread::panicking()
             }
         }
 
-        FutexWait::new(addr, self).await;
+        // The blocking thread taken down by a panic is replaced, and the pool
+        // can still be shut down
+        let rt = Builder::new()
+            .max_blocking_threads(1)
+            .unwinder(NoUnwinder)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let join_handle = crate::task::spawn_blocking(|| {
+                panic!("a panic that is expected by the test");
+            });
+            assert!(join_handle.await == Err(JoinError::Panicked));
+            assert!(crate::task::spawn_blocking(|| 1).await == Ok(1));
+        });
+        assert!(rt.shutdown_graceful(Duration::from_secs(10)).is_ok());
     }
 
-    pub fn wake(&self, addr: &AtomicI32, max_count: usize) -> usize {
-        let mut table = self.table.lock().unwrap();
-        let addr = addr as *const AtomicI32 as usize;
-        let wakers = match table.get_mut(&addr) {
-            None => return 0,
-            Some(wakers) => wakers,
+    #[test]
+    fn test_actors() {
+        use crate::runtime::{Actor, Builder};
+        use std::sync::atomic::AtomicUsize;
+
+        let num_runs = Arc::new(AtomicUsize::new(0));
+        let is_misplaced = Arc::new(AtomicBool::new(false));
+        let runtime = Builder::new().worker_threads(2).build().unwrap();
+        let handle = runtime.handle().clone();
+
+        // An actor bound to one thread is only run by that thread
+        let actor = {
+            let num_runs = num_runs.clone();
+            let is_misplaced = is_misplaced.clone();
+            Actor::new(move || {
+                if crate::sched::current_thread_id() != Some(1) {
+                    is_misplaced.store(true, Ordering::Relaxed);
+                }
+                num_runs.fetch_add(1, Ordering::Relaxed);
+                false
+            })
+            .thread_id(1)
+            .interval(2)
         };
-        let mut count = 0;
-        for _ in 0..max_count {
-            let waker = match wakers.pop() {
-                None => break,
-                Some(waker) => waker,
+        let actor_id = handle.register_actor(actor).unwrap();
+        while num_runs.load(Ordering::Relaxed) < 100 {
+            std::thread::yield_now();
+        }
+        assert!(!is_misplaced.load(Ordering::Relaxed));
+
+        // A removed actor is no longer run, except for a run in progress
+        handle.unregister_actor(actor_id).unwrap();
+        let num_runs_after_removal = num_runs.load(Ordering::Relaxed);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(num_runs.load(Ordering::Relaxed) <= num_runs_after_removal + 1);
+        assert!(handle.unregister_actor(actor_id).is_err());
+
+        // Invalid actors are rejected
+        let out_of_range = Actor::new(|| false).thread_id(2);
+        assert!(handle.register_actor(out_of_range).is_err());
+        let zero_interval = Actor::new(|| false).interval(0);
+        assert!(handle.register_actor(zero_interval).is_err());
+    }
+
+    #[test]
+    fn test_metrics_and_dump() {
+        use crate::runtime::{Builder, Handle};
+        use crate::sched::Priority;
+        use crate::sync::Notify;
+        use crate::task::TaskState;
+
+        let runtime = Builder::new().worker_threads(2).build().unwrap();
+        let handle = runtime.handle().clone();
+        runtime.block_on(async {
+            let notify = Arc::new(Notify::new());
+            let is_started = Arc::new(AtomicBool::new(false));
+            let waiter = {
+                let notify = notify.clone();
+                let is_started = is_started.clone();
+                crate::task::spawn_with_priority(Priority::Low, async move {
+                    is_started.store(true, Ordering::Release);
+                    notify.notified().await;
+                })
             };
-            waker.wake();
-            count += 1;
+            // The waiter becomes idle shortly after it has started
+            while !is_started.load(Ordering::Acquire) {
+                crate::sched::yield_().await;
+            }
+            let tasks = loop {
+                let tasks = crate::task::dump();
+                if tasks[1].state == TaskState::Idle {
+                    break tasks;
+                }
+                crate::sched::yield_().await;
+            };
+
+            assert!(tasks.len() == 2);
+            let current = &tasks[0];
+            assert!(current.tid == crate::task::current().tid());
+            assert!(current.state == TaskState::Running);
+            let idle = &tasks[1];
+            assert!(idle.tid == waiter.task_id());
+            assert!(idle.priority == Priority::Low);
+            assert!(idle.affinity.is_full());
+
+            let metrics = Handle::current().metrics();
+            assert!(metrics.num_tasks == 2);
+            assert!(metrics.workers.len() == 2);
+            let tasks_polled: u64 = metrics.workers.iter().map(|w| w.tasks_polled).sum();
+            assert!(tasks_polled >= 2);
+
+            notify.notify_one();
+            waiter.await.unwrap();
+        });
+
+        // Both are usable from outside the runtime
+        assert!(handle.dump_tasks().is_empty());
+        assert!(handle.metrics().num_tasks == 0);
+
+        // Without timing, the tasks are still counted but not timed
+        let runtime = Builder::new().timing_metrics(false).build().unwrap();
+        runtime.block_on(async {
+            crate::time::sleep(core::time::Duration::from_millis(1)).await;
+        });
+        let metrics = runtime.handle().metrics();
+        assert!(metrics.workers[0].tasks_polled >= 2);
+        assert!(metrics.workers[0].poll_time == core::time::Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_multiple_runtimes() {
+        use crate::runtime::{Builder, Handle};
+
+        let runtimes: Vec<_> = (1..=2)
+            .map(|parallelism| {
+                Builder::new()
+                    .worker_threads(parallelism)
+                    .thread_name(format!("rt{}", parallelism))
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        for (i, runtime) in runtimes.iter().enumerate() {
+            let parallelism = (i + 1) as u32;
+            let thread_name = format!("rt{}-", parallelism);
+            let output = runtime.block_on(async move {
+                // The spawned task runs on the same runtime as its parent
+                let join_handle = crate::task::spawn(async {
+                    let thread = std::thread::current();
+                    let thread_name = thread.name().unwrap().to_string();
+                    (Handle::current().parallelism(), thread_name)
+                });
+                join_handle.await.unwrap()
+            });
+            assert!(output.0 == parallelism);
+            assert!(output.1.starts_with(&thread_name));
         }
-        count
+
+        // Dropping the runtimes stops their worker threads
+        drop(runtimes);
     }
-}
 
-struct FutexWait<'a> {
-    polled_once: bool,
-    addr: &'a AtomicI32,
-    futex_table: &'a FutexTable,
-}
+    #[test]
+    fn test_drop_runtime_on_worker() {
+        use crate::runtime::Builder;
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        // Dropping a runtime from one of its own tasks does not deadlock
+        let runtime = Builder::new().worker_threads(2).build().unwrap();
+        let handle = runtime.handle().clone();
+        let (sender, receiver) = mpsc::channel();
+        handle.spawn(async move {
+            drop(runtime);
+            sender.send(()).unwrap();
+        });
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_global_executor() {
+        crate::executor::set_parallelism(2).unwrap();
+        let threads: Vec<_> = (0..2)
+            .map(|_| std::thread::spawn(crate::executor::run_tasks))
+            .collect();
+        assert!(crate::executor::parallelism() == 2);
+
+        let output = crate::task::block_on(async {
+            let join_handle = crate::task::spawn(async { 1 });
+            join_handle.await.unwrap()
+        });
+        assert!(output == 1);
 
-impl<'a> FutexWait<'a> {
-    pub fn new(addr: &'a AtomicI32, futex_table: &'a FutexTable) -> Self {
-        let polled_once = false;
-        Self {
-            polled_once,
-            addr,
-            futex_table,
+        crate::executor::shutdown();
+        for thread in threads {
+            thread.join().unwrap();
         }
     }
-}
 
-impl<'a> Future for FutexWait<'a> {
-    type Output = ();
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let self_ = self.get_mut();
-        if self_.polled_once {
-            return Poll::Ready(());
+    #[test]
+    fn test_deterministic_runtime() {
+        use crate::runtime::Builder;
+
+        // Run some tasks that interleave at their yield points
+        let run = |seed: u64| {
+            let runtime = Builder::new().deterministic(seed).build().unwrap();
+            assert!(runtime.handle().seed() == Some(seed));
+            let trace = Arc::new(Mutex::new(Vec::new()));
+            let trace_ = trace.clone();
+            runtime.block_on(async move {
+                let join_handles: Vec<_> = (0..5)
+                    .map(|i| {
+                        let trace = trace_.clone();
+                        crate::task::spawn(async move {
+                            for _ in 0..3 {
+                                trace.lock().push(i);
+                                crate::sched::yield_().await;
+                            }
+                            crate::sim::random_index(100)
+                        })
+                    })
+                    .collect();
+                for join_handle in join_handles {
+                    let output = join_handle.await.unwrap();
+                    trace_.lock().push(output);
+                }
+            });
+            let trace = trace.lock().clone();
+            trace
+        };
+
+        // The same seed replays the same interleaving
+        assert!(run(1) == run(1));
+        assert!((2..10).any(|seed| run(seed) != run(1)));
+
+        assert!(Builder::new()
+            .worker_threads(2)
+            .deterministic(1)
+            .build()
+            .is_err());
+        assert!(crate::sim::random_index(10) == 0);
+    }
+
+    #[test]
+    fn test_worker_cpus() {
+        use crate::runtime::Builder;
+
+        // Record the CPU that each worker thread pins itself to
+        let pinned = Arc::new(Mutex::new(Vec::new()));
+        let pinned_ = pinned.clone();
+        let runtime = Builder::new()
+            .worker_threads(2)
+            .worker_cpus(vec![5, 7])
+            .cpu_pinner(move |cpu| {
+                let thread_id = crate::executor::current_thread_id().unwrap();
+                pinned_.lock().push((thread_id, cpu));
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        assert!(runtime.handle().worker_cpu(1) == Some(7));
+        assert!(runtime.handle().worker_cpu(2) == None);
+
+        let pinned_ = pinned.clone();
+        runtime.block_on(async move {
+            while pinned_.lock().len() < 2 {
+                crate::sched::yield_().await;
+            }
+        });
+        let mut pinned = pinned.lock().clone();
+        pinned.sort();
+        assert!(pinned == vec![(0, 5), (1, 7)]);
+
+        assert!(Builder::new()
+            .worker_threads(2)
+            .worker_cpus(vec![0])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_block_on_in_task() {
+        use crate::runtime::Handle;
+
+        crate::test_rt::run_blocking(async {
+            // Block every worker thread, which has to keep running other tasks
+            const NUM_TASKS: usize = 10;
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|i| {
+                    crate::task::spawn(async move {
+                        let tid = crate::task::current().tid();
+                        let output = Handle::current().block_on(async move {
+                            crate::sched::yield_().await;
+                            crate::task::spawn(async move { i }).await.unwrap()
+                        });
+                        assert!(crate::task::current().tid() == tid);
+                        output
+                    })
+                })
+                .collect();
+            for (i, join_handle) in join_handles.into_iter().enumerate() {
+                assert!(join_handle.await == Ok(i));
+            }
+        });
+    }
+
+    #[test]
+    fn test_block_on_parking() {
+        use crate::executor::ThreadParker;
+        use crate::runtime::{Builder, Handle};
+        use crate::sync::oneshot;
+        use std::sync::atomic::AtomicUsize;
+        use std::time::{Duration, Instant};
+
+        static NUM_PARKERS: AtomicUsize = AtomicUsize::new(0);
+
+        // A thread that blocks on the runtime is parked with a parker of the runtime
+        let runtime = Builder::new()
+            .worker_threads(2)
+            .parker(|| {
+                NUM_PARKERS.fetch_add(1, Ordering::Relaxed);
+                ThreadParker::new()
+            })
+            .build()
+            .unwrap();
+        while NUM_PARKERS.load(Ordering::Relaxed) < 2 {
+            std::thread::yield_now();
         }
-        self_.polled_once = true;
+        assert!(runtime.block_on(async { 1 }) == 1);
+        assert!(NUM_PARKERS.load(Ordering::Relaxed) == 3);
 
-        let addr = self_.addr as *const AtomicI32 as usize;
-        let mut table = self_.futex_table.table.lock().unwrap();
-        let wakers = table
-            .entry(addr)
-            .or_insert(Vec::new());
-        wakers.push(cx.waker().clone());
-        Poll::Pending
+        // Deeply nested calls park the worker thread, whose tasks are stolen
+        fn nested(depth: usize) -> BoxFuture<'static, usize> {
+            async move {
+                if depth == 0 {
+                    return 0;
+                }
+                Handle::current().block_on(nested(depth - 1)) + 1
+            }
+            .boxed()
+        }
+        assert!(runtime.block_on(nested(24)) == 24);
+
+        // A worker thread that blocks on a future after shutdown is parked
+        // instead of polling the future over and over again
+        let runtime = Builder::new().worker_threads(1).build().unwrap();
+        let handle = runtime.handle().clone();
+        let join_handle = handle.spawn(async move {
+            drop(runtime);
+            let (sender, mut receiver) = oneshot::channel::<()>();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                sender.send(()).unwrap();
+            });
+            let mut num_polls = 0;
+            let executor = crate::executor::current().unwrap();
+            let _ = executor.block_on(futures::future::poll_fn(|cx| {
+                num_polls += 1;
+                Pin::new(&mut receiver).poll(cx)
+            }));
+            num_polls
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !join_handle.is_finished() {
+            assert!(Instant::now() < deadline);
+            std::thread::yield_now();
+        }
+        let num_polls = join_handle.now_or_never().unwrap().unwrap();
+        assert!(num_polls < 10);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_graceful_shutdown() {
+        use crate::runtime::Builder;
+        use crate::task::JoinError;
+        use std::sync::atomic::AtomicUsize;
+        use std::time::Duration;
+
+        static NUM_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Guard;
 
-    macro_rules! run_tasks {
-        ($($async_code: expr),+,) => {{
-            let (executor, spawner) = new_executor_and_spawner();
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                NUM_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
 
-            $(
-                spawner.spawn($async_code);
-            )*
+        // The in-flight tasks that finish in time are joined as usual
+        let runtime = Builder::new().worker_threads(2).build().unwrap();
+        let handle = runtime.handle().clone();
+        let join_handle = handle.spawn(async {
+            crate::time::sleep(Duration::from_millis(10)).await;
+            1
+        });
+        assert!(runtime.shutdown_graceful(Duration::from_secs(10)).is_ok());
+        assert!(crate::test_rt::run_blocking(join_handle) == Ok(1));
 
-            drop(spawner);
-            executor.run();
-        }};
-        ($($async_code: expr),+) => {{
-            run_tasks!($($async_code),*,)
-        }}
+        // The tasks that do not finish in time are cancelled
+        let runtime = Builder::new().worker_threads(2).build().unwrap();
+        let handle = runtime.handle().clone();
+        let join_handle = handle.spawn(async {
+            let _guard = Guard;
+            crate::time::sleep(Duration::from_secs(3600)).await;
+        });
+        assert!(runtime.shutdown_graceful(Duration::from_millis(10)).is_err());
+        assert!(NUM_DROPPED.load(Ordering::Relaxed) == 1);
+        assert!(crate::test_rt::run_blocking(join_handle) == Err(JoinError::Shutdown));
+
+        // No more tasks are accepted after shutdown
+        let join_handle = handle.spawn(async { 1 });
+        assert!(crate::test_rt::run_blocking(join_handle) == Err(JoinError::Shutdown));
+        assert!(handle.try_spawn(async { 1 }).is_err());
+
+        // The blocking jobs are done before shutdown returns
+        static IS_JOB_DONE: AtomicBool = AtomicBool::new(false);
+        let runtime = Builder::new().build().unwrap();
+        let _ = runtime.handle().spawn_blocking(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            IS_JOB_DONE.store(true, Ordering::Relaxed);
+        });
+        assert!(runtime.shutdown_graceful(Duration::from_secs(10)).is_ok());
+        assert!(IS_JOB_DONE.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_sync_mutex() {
+        use crate::sync::Mutex;
+
+        crate::test_rt::run_blocking(async {
+            const NUM_TASKS: usize = 10;
+            const NUM_ROUNDS: usize = 100;
+            let counter = Arc::new(Mutex::new(0));
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|_| {
+                    let counter = counter.clone();
+                    crate::task::spawn(async move {
+                        for _ in 0..NUM_ROUNDS {
+                            // Hold the lock across an await point
+                            let mut count = counter.lock().await;
+                            let old_count = *count;
+                            crate::sched::yield_().await;
+                            *count = old_count + 1;
+                        }
+                    })
+                })
+                .collect();
+            for join_handle in join_handles {
+                join_handle.await.unwrap();
+            }
+            assert!(*counter.lock().await == NUM_TASKS * NUM_ROUNDS);
+        });
+    }
+
+    #[test]
+    fn test_sync_rwlock_and_semaphore() {
+        use crate::sync::{RwLock, Semaphore};
+
+        crate::test_rt::run_blocking(async {
+            let lock = RwLock::new(0);
+            let read_guard0 = lock.read().await;
+            let read_guard1 = lock.read().await;
+            assert!(lock.try_write().is_none());
+            drop(read_guard0);
+            drop(read_guard1);
+            *lock.write().await = 1;
+            assert!(lock.try_read().is_some());
+            assert!(*lock.read().await == 1);
+
+            let semaphore = Arc::new(Semaphore::new(2));
+            let permits = semaphore.acquire_many(2).await;
+            assert!(semaphore.try_acquire().is_none());
+            let join_handle = {
+                let semaphore = semaphore.clone();
+                crate::task::spawn(async move {
+                    semaphore.acquire().await.forget();
+                })
+            };
+            crate::sched::yield_().await;
+            assert!(!join_handle.is_finished());
+            drop(permits);
+            join_handle.await.unwrap();
+            assert!(semaphore.available_permits() == 1);
+        });
+    }
+
+    #[test]
+    fn test_sync_notify_and_barrier() {
+        use crate::sync::{Barrier, Notify};
+
+        crate::test_rt::run_blocking(async {
+            // A notification is stored if no task is waiting
+            let notify = Arc::new(Notify::new());
+            notify.notify_one();
+            notify.notified().await;
+
+            let join_handle = {
+                let notify = notify.clone();
+                crate::task::spawn(async move { notify.notified().await })
+            };
+            while !join_handle.is_finished() {
+                notify.notify_waiters();
+                crate::sched::yield_().await;
+            }
+
+            const NUM_TASKS: usize = 5;
+            let barrier = Arc::new(Barrier::new(NUM_TASKS));
+            let join_handles: Vec<_> = (0..NUM_TASKS)
+                .map(|_| {
+                    let barrier = barrier.clone();
+                    crate::task::spawn(async move { barrier.wait().await })
+                })
+                .collect();
+            let mut num_leaders = 0;
+            for join_handle in join_handles {
+                if join_handle.await.unwrap() {
+                    num_leaders += 1;
+                }
+            }
+            assert!(num_leaders == 1);
+        });
+    }
+
+    #[test]
+    fn test_sync_channels() {
+        use crate::sync::{oneshot, watch};
+
+        crate::test_rt::run_blocking(async {
+            let (sender, receiver) = oneshot::channel();
+            crate::task::spawn(async move {
+                crate::sched::yield_().await;
+                sender.send(1).unwrap();
+            });
+            assert!(receiver.await == Ok(1));
+
+            let (sender, receiver) = oneshot::channel::<i32>();
+            drop(sender);
+            assert!(receiver.await.is_err());
+
+            let (sender, mut receiver) = watch::channel(0);
+            let join_handle = crate::task::spawn(async move {
+                let mut values = Vec::new();
+                while receiver.changed().await.is_ok() {
+                    values.push(*receiver.borrow());
+                }
+                values
+            });
+            for i in 1..=3 {
+                sender.send(i).unwrap();
+                crate::time::sleep(core::time::Duration::from_millis(1)).await;
+            }
+            drop(sender);
+            let values = join_handle.await.unwrap();
+            assert!(values.last() == Some(&3));
+        });
     }
 
     #[test]
     fn test_futex() {
-        let futex_val = Arc::new(AtomicI32::new(10));
-        let futex_val2 = futex_val.clone();
-        run_tasks!(
-            wait_wake(futex_val, true),
-            wait_wake(futex_val2, false),
-        );
-    }
-
-    async fn wait_wake(futex_val: Arc<AtomicI32>, wait_on_odd: bool) {
-        let futex_val = futex_val.clone();
-        loop {
-            let val = futex_val.load(Ordering::Acquire);
-            if val == 0 {
-                break;
-            }
-
-            if (val % 2 == 1) ^ wait_on_odd {
-                println!("futex wait (val = {:?})", val);
-                FUTEX_TABLE.wait(futex_val.deref(), val).await;
-            } else {
-                let new_val = futex_val.fetch_sub(1, Ordering::Release);
-                println!("futex wake (val = {:?})", new_val);
-                FUTEX_TABLE.wake(futex_val.deref(), 1);
+        use crate::sync::futex::{self, FutexError};
+        use core::sync::atomic::AtomicI32;
+        use core::time::Duration;
+
+        async fn wait_wake(futex_val: Arc<AtomicI32>, wait_on_odd: bool) {
+            loop {
+                let val = futex_val.load(Ordering::Acquire);
+                if val == 0 {
+                    break;
+                }
+
+                if (val % 2 == 1) ^ wait_on_odd {
+                    let _ = futex::wait(&futex_val, val, None).await;
+                } else {
+                    futex_val.fetch_sub(1, Ordering::Release);
+                    futex::wake(&futex_val, 1);
+                }
+            }
+        }
+
+        crate::test_rt::run_blocking(async {
+            let futex_val = Arc::new(AtomicI32::new(100));
+            let join_handles = vec![
+                crate::task::spawn(wait_wake(futex_val.clone(), true)),
+                crate::task::spawn(wait_wake(futex_val.clone(), false)),
+            ];
+            for join_handle in join_handles {
+                join_handle.await.unwrap();
+            }
+
+            let futex_val = Arc::new(AtomicI32::new(0));
+            let res = futex::wait(&futex_val, 1, None).await;
+            assert!(res == Err(FutexError::WouldBlock));
+            let res = futex::wait(&futex_val, 0, Some(Duration::from_millis(1))).await;
+            assert!(res == Err(FutexError::TimedOut));
+
+            // Requeue a waiter, which can then only be woken up via the new futex
+            let new_futex_val = Arc::new(AtomicI32::new(0));
+            let join_handle = {
+                let futex_val = futex_val.clone();
+                crate::task::spawn(
+                    async move { futex::wait_bitset(&futex_val, 0, None, 0x1).await },
+                )
+            };
+            while futex::requeue(&futex_val, 0, &new_futex_val, 1) == 0 {
+                crate::sched::yield_().await;
+            }
+            assert!(futex::wake(&futex_val, 1) == 0);
+            assert!(futex::wake_bitset(&new_futex_val, 1, 0x2) == 0);
+            assert!(futex::wake_bitset(&new_futex_val, 1, 0x3) == 1);
+            assert!(join_handle.await == Ok(Ok(())));
+        });
+    }
+
+    #[test]
+    fn test_futex_wake_racing_timeout() {
+        use crate::sync::futex::{self, FUTEX_BITSET_MATCH_ANY};
+        use core::sync::atomic::AtomicI32;
+        use core::time::Duration;
+
+        crate::test_rt::run_blocking(async {
+            // A waiter that is woken up at about its deadline is never reported
+            // as timed out, and vice versa
+            for _ in 0..100 {
+                let futex_val = Arc::new(AtomicI32::new(0));
+                let join_handle = {
+                    let futex_val = futex_val.clone();
+                    let deadline = crate::time::now() + Duration::from_millis(1);
+                    crate::task::spawn(async move {
+                        futex::wait_bitset(&futex_val, 0, Some(deadline), FUTEX_BITSET_MATCH_ANY)
+                            .await
+                    })
+                };
+                crate::time::sleep(Duration::from_millis(1)).await;
+                let num_woken = futex::wake(&futex_val, 1);
+                let res = join_handle.await.unwrap();
+                assert!((num_woken == 1) == res.is_ok());
+            }
+        });
+    }
+
+    #[test]
+    fn test_sleep() {
+        crate::test_rt::run_blocking(async {
+            use core::time::Duration;
+
+            let duration = Duration::from_millis(50);
+            let start = crate::time::now();
+            crate::time::sleep(duration).await;
+            assert!(crate::time::now() - start >= duration);
+        });
+    }
+
+    #[test]
+    fn test_timers_per_runtime() {
+        use crate::runtime::Builder;
+        use core::time::Duration;
+
+        let runtimes: Vec<_> = (0..2)
+            .map(|_| Builder::new().worker_threads(1).build().unwrap())
+            .collect();
+        let has_timers = |i: usize| runtimes[i].handle().executor().timers().has_timers();
+
+        // A sleeping task keeps its timer in the wheel of its own runtime
+        let join_handle = runtimes[0].spawn(async {
+            crate::time::sleep(Duration::from_millis(100)).await;
+        });
+        while !has_timers(0) {
+            std::thread::yield_now();
+        }
+        assert!(!has_timers(1));
+
+        runtimes[0].block_on(join_handle).unwrap();
+        assert!(!has_timers(0));
+
+        // A cancelled timer stops counting at once, long before its deadline
+        let join_handle = runtimes[1].spawn(async {
+            let res = crate::time::timeout(Duration::from_secs(60), async {
+                crate::sched::yield_().await;
+                1
+            })
+            .await;
+            assert!(res == Ok(1));
+            crate::executor::current().unwrap().timers().num_timers()
+        });
+        assert!(runtimes[1].block_on(join_handle) == Ok(0));
+        assert!(!has_timers(1));
+    }
+
+    #[test]
+    fn test_timeout() {
+        crate::test_rt::run_blocking(async {
+            use core::time::Duration;
+
+            let res = crate::time::timeout(Duration::from_millis(10), async {
+                crate::time::sleep(Duration::from_secs(60)).await;
+            })
+            .await;
+            assert!(res.is_err());
+
+            let res = crate::time::timeout(Duration::from_secs(60), async { 1 }).await;
+            assert!(res == Ok(1));
+        });
+    }
+
+    #[test]
+    fn test_interval() {
+        crate::test_rt::run_blocking(async {
+            use core::time::Duration;
+
+            let period = Duration::from_millis(10);
+            let mut interval = crate::time::interval(period);
+            let start = interval.tick().await;
+            for i in 1..5 {
+                let deadline = interval.tick().await;
+                assert!(deadline == start + period * i);
+                assert!(crate::time::now() >= deadline);
+            }
+        });
+    }
+
+    #[test]
+    fn test_park_and_unpark() {
+        // Give the executor threads enough time to become idle and park
+        crate::test_rt::run_blocking(async {});
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        crate::test_rt::run_blocking(async {
+            let join_handles: Vec<_> = (0..10)
+                .map(|i| crate::task::spawn(async move { i }))
+                .collect();
+            for (i, join_handle) in join_handles.into_iter().enumerate() {
+                assert!(join_handle.await == Ok(i));
+            }
+        });
+    }
+}
+
+// Test runtime
+#[cfg(test)]
+mod test_rt {
+    use crate::prelude::*;
+    use crate::runtime::{Builder, Runtime};
+
+    pub(crate) fn run_blocking<T: Send + 'static>(
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> T {
+        TEST_RT.block_on(future)
+    }
+
+    const TEST_PARALLELISM: u32 = 3;
+
+    lazy_static! {
+        static ref TEST_RT: Runtime = {
+            crate::test_logger::init().unwrap();
+
+            Builder::new()
+                .worker_threads(TEST_PARALLELISM)
+                .thread_name("test-rt")
+                .build()
+                .unwrap()
+        };
+    }
+}
+
+#[cfg(test)]
+mod test_logger {
+    use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+
+    static LOGGER: SimpleLogger = SimpleLogger;
+
+    pub fn init() -> std::result::Result<(), SetLoggerError> {
+        log::set_logger(&LOGGER).map(|()| log::set_max_level(LevelFilter::Info))
+    }
+
+    struct SimpleLogger;
+
+    impl log::Log for SimpleLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                println!("[{}] {}", record.level(), record.args());
             }
         }
+
+        fn flush(&self) {}
     }
 }
-*/
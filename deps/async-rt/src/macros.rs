@@ -20,3 +20,211 @@ macro_rules! task_local {
         self::task_local!($($rest)*);
     );
 }
+
+/// Await multiple futures concurrently and return a tuple of their outputs.
+///
+/// ```ignore
+/// let (a, b) = join!(fetch_a(), fetch_b());
+/// ```
+#[macro_export]
+macro_rules! join {
+    (@ { ( $($count:tt)* ) $( ( $($skip:tt)* ) $e:expr, )* }) => {{
+        use $crate::future::support::{maybe_done, poll_fn, start_index, Future, Pin, Poll};
+
+        const COUNT: usize = $crate::__count!($($count)*);
+        let mut futures = ( $( maybe_done($e), )* );
+        // The futures are never moved once pinned
+        let futures = &mut futures;
+        // Each invocation starts from a different future, and each poll from
+        // the next one, as `Race` does
+        let mut next_start = start_index(COUNT);
+        poll_fn(move |cx| {
+            let start = next_start;
+            next_start = (next_start + 1) % COUNT;
+            let mut is_pending = false;
+            for i in 0..COUNT {
+                let branch = (start + i) % COUNT;
+                $(
+                    if branch == $crate::__count!($($skip)*) {
+                        let ( $($skip,)* fut, .. ) = &mut *futures;
+                        let fut = unsafe { Pin::new_unchecked(fut) };
+                        is_pending |= fut.poll(cx).is_pending();
+                    }
+                )*
+            }
+            if is_pending {
+                return Poll::Pending;
+            }
+
+            Poll::Ready(( $({
+                let ( $($skip,)* fut, .. ) = &mut *futures;
+                let fut = unsafe { Pin::new_unchecked(fut) };
+                fut.take_output().unwrap()
+            }, )* ))
+        })
+        .await
+    }};
+
+    (@ { ( $($s:tt)* ) $($t:tt)* } $e:expr, $($r:tt)* ) => {
+        $crate::join!(@ { ( $($s)* _ ) $($t)* ( $($s)* ) $e, } $($r)*)
+    };
+
+    ( $($e:expr),+ $(,)? ) => {
+        $crate::join!(@ { () } $($e,)+)
+    };
+}
+
+/// Await multiple futures that return `Result`s concurrently.
+///
+/// Returns a tuple of their outputs if all of them succeed, or the first error
+/// otherwise, in which case the other futures are dropped. The futures must
+/// have the same error type.
+///
+/// ```ignore
+/// let (a, b) = try_join!(fetch_a(), fetch_b())?;
+/// ```
+#[macro_export]
+macro_rules! try_join {
+    (@ { ( $($count:tt)* ) $( ( $($skip:tt)* ) $e:expr, )* }) => {{
+        use $crate::future::support::{maybe_done, poll_fn, start_index, Future, Pin, Poll};
+
+        const COUNT: usize = $crate::__count!($($count)*);
+        let mut futures = ( $( maybe_done($e), )* );
+        // The futures are never moved once pinned
+        let futures = &mut futures;
+        // Each invocation starts from a different future, and each poll from
+        // the next one, as `Race` does
+        let mut next_start = start_index(COUNT);
+        poll_fn(move |cx| {
+            let start = next_start;
+            next_start = (next_start + 1) % COUNT;
+            let mut is_pending = false;
+            for i in 0..COUNT {
+                let branch = (start + i) % COUNT;
+                $(
+                    if branch == $crate::__count!($($skip)*) {
+                        let ( $($skip,)* fut, .. ) = &mut *futures;
+                        let mut fut = unsafe { Pin::new_unchecked(fut) };
+                        if fut.as_mut().poll(cx).is_pending() {
+                            is_pending = true;
+                        } else if let Some(Err(_)) = fut.as_mut().output_mut() {
+                            return Poll::Ready(Err(fut.take_output().unwrap().err().unwrap()));
+                        }
+                    }
+                )*
+            }
+            if is_pending {
+                return Poll::Pending;
+            }
+
+            Poll::Ready(Ok(( $({
+                let ( $($skip,)* fut, .. ) = &mut *futures;
+                let fut = unsafe { Pin::new_unchecked(fut) };
+                match fut.take_output() {
+                    Some(Ok(output)) => output,
+                    _ => unreachable!(),
+                }
+            }, )* )))
+        })
+        .await
+    }};
+
+    (@ { ( $($s:tt)* ) $($t:tt)* } $e:expr, $($r:tt)* ) => {
+        $crate::try_join!(@ { ( $($s)* _ ) $($t)* ( $($s)* ) $e, } $($r)*)
+    };
+
+    ( $($e:expr),+ $(,)? ) => {
+        $crate::try_join!(@ { () } $($e,)+)
+    };
+}
+
+/// Await multiple futures concurrently and run the handler of the one that
+/// completes first.
+///
+/// Each branch is in the form of `<pattern> = <future> => <handler>`, where the
+/// pattern must be irrefutable. Once a future completes, the other futures are
+/// dropped, and then its output is bound to the pattern and its handler is run.
+/// The handlers may use `return`, `break` and `?` as in the enclosing code.
+///
+/// ```ignore
+/// select! {
+///     res = sock.read(buf) => res?,
+///     _ = shutdown.notified() => return Ok(0),
+/// }
+/// ```
+#[macro_export]
+macro_rules! select {
+    (@ { ( $($count:tt)* ) $( ( $($skip:tt)* ) $p:pat = $e:expr => $handler:expr, )* }) => {{
+        use $crate::future::support::{output_slot, poll_fn, start_index, Future, Pin, Poll};
+
+        const COUNT: usize = $crate::__count!($($count)*);
+        let (branch, mut outputs) = {
+            let mut futures = ( $( $e, )* );
+            // The futures are never moved once pinned
+            let futures = &mut futures;
+            let mut outputs = ( $({
+                let ( $($skip,)* fut, .. ) = &*futures;
+                output_slot(fut)
+            }, )* );
+            // Each invocation starts from a different future, and each poll
+            // from the next one, as `Race` does
+            let mut next_start = start_index(COUNT);
+            let branch = poll_fn(|cx| {
+                let start = next_start;
+                next_start = (next_start + 1) % COUNT;
+                for i in 0..COUNT {
+                    let branch = (start + i) % COUNT;
+                    $(
+                        if branch == $crate::__count!($($skip)*) {
+                            let ( $($skip,)* fut, .. ) = &mut *futures;
+                            let fut = unsafe { Pin::new_unchecked(fut) };
+                            if let Poll::Ready(output) = fut.poll(cx) {
+                                let ( $($skip,)* slot, .. ) = &mut outputs;
+                                *slot = Some(output);
+                                return Poll::Ready(branch);
+                            }
+                        }
+                    )*
+                }
+                Poll::Pending
+            })
+            .await;
+            (branch, outputs)
+        };
+
+        $(
+            if branch == $crate::__count!($($skip)*) {
+                let ( $($skip,)* slot, .. ) = &mut outputs;
+                let $p = slot.take().unwrap();
+                $handler
+            } else
+        )*
+        {
+            unreachable!()
+        }
+    }};
+
+    (@ { ( $($s:tt)* ) $($t:tt)* } $p:pat = $e:expr => $handler:block $(,)? $($r:tt)* ) => {
+        $crate::select!(@ { ( $($s)* _ ) $($t)* ( $($s)* ) $p = $e => $handler, } $($r)*)
+    };
+
+    (@ { ( $($s:tt)* ) $($t:tt)* } $p:pat = $e:expr => $handler:expr, $($r:tt)* ) => {
+        $crate::select!(@ { ( $($s)* _ ) $($t)* ( $($s)* ) $p = $e => $handler, } $($r)*)
+    };
+
+    (@ { ( $($s:tt)* ) $($t:tt)* } $p:pat = $e:expr => $handler:expr ) => {
+        $crate::select!(@ { ( $($s)* _ ) $($t)* ( $($s)* ) $p = $e => $handler, })
+    };
+
+    ( $($t:tt)+ ) => {
+        $crate::select!(@ { () } $($t)+)
+    };
+}
+
+// Count the tokens, which is used by the macros above to number the futures
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __count {
+    () => { 0usize };
+    ($head:tt $($tail:tt)*) => { 1usize + $crate::__count!($($tail)*) };
+}
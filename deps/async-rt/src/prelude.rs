@@ -9,6 +9,6 @@ pub use core::future::Future;
 pub use core::pin::Pin;
 pub use futures::future::{BoxFuture, FutureExt};
 
-pub use crate::task_local;
+pub use crate::{join, select, task_local, try_join};
 
 pub type Result<T> = core::result::Result<T, &'static str>;
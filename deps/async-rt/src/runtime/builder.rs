@@ -0,0 +1,304 @@
+use alloc::format;
+use alloc::string::String;
+
+use crate::executor::{
+    Actor, BlockingPool, CpuPinner, Executor, ExecutorConfig, Parker, ParkerFactory, ThreadSpawner,
+    Unwinder, WorkerCpus,
+};
+use crate::prelude::*;
+use crate::runtime::{Handle, Runtime};
+use crate::sim::Simulation;
+
+const DEFAULT_WORKER_THREADS: u32 = 1;
+const DEFAULT_MAX_QUEUED_TASKS: u32 = 1_000;
+const DEFAULT_MAX_BLOCKING_THREADS: u32 = 16;
+const DEFAULT_THREAD_NAME: &str = "async-rt-worker";
+
+/// A builder of runtimes.
+///
+/// # Examples
+///
+/// ```ignore
+/// let rt = Builder::new()
+///     .worker_threads(4)
+///     .thread_name("my-worker")
+///     .build()
+///     .unwrap();
+/// rt.block_on(async { /* ... */ });
+/// ```
+pub struct Builder {
+    worker_threads: u32,
+    max_queued_tasks: u32,
+    max_blocking_threads: u32,
+    thread_name: String,
+    actors: Vec<Actor>,
+    parker: Option<ParkerFactory>,
+    thread_spawner: Option<ThreadSpawner>,
+    seed: Option<u64>,
+    worker_cpus: Option<Vec<u32>>,
+    cpu_pinner: Option<CpuPinner>,
+    unwinder: Option<Arc<dyn Unwinder>>,
+    timing_metrics: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            worker_threads: DEFAULT_WORKER_THREADS,
+            max_queued_tasks: DEFAULT_MAX_QUEUED_TASKS,
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+            thread_name: String::from(DEFAULT_THREAD_NAME),
+            actors: Vec::new(),
+            parker: None,
+            thread_spawner: None,
+            seed: None,
+            worker_cpus: None,
+            cpu_pinner: None,
+            unwinder: None,
+            timing_metrics: true,
+        }
+    }
+
+    /// Set the number of worker threads, which must be non-zero.
+    pub fn worker_threads(mut self, worker_threads: u32) -> Self {
+        self.worker_threads = worker_threads;
+        self
+    }
+
+    /// Set the capacity of the run queue of each worker thread, per priority.
+    ///
+    /// Tasks that do not fit in a run queue are moved to a global overflow queue,
+    /// which is unbounded. So this is not a hard limit on the number of tasks,
+    /// but the point at which `task::try_spawn` starts to reject new tasks.
+    pub fn max_queued_tasks(mut self, max_queued_tasks: u32) -> Self {
+        self.max_queued_tasks = max_queued_tasks;
+        self
+    }
+
+    /// Set the max number of the threads that run blocking jobs, which must be non-zero.
+    ///
+    /// These threads are spawned on demand by `task::spawn_blocking`, apart
+    /// from the worker threads.
+    pub fn max_blocking_threads(mut self, max_blocking_threads: u32) -> Self {
+        self.max_blocking_threads = max_blocking_threads;
+        self
+    }
+
+    /// Set the name of the worker threads, which are suffixed with their indexes.
+    pub fn thread_name(mut self, thread_name: impl Into<String>) -> Self {
+        self.thread_name = thread_name.into();
+        self
+    }
+
+    /// Add an actor, which is either an `Actor` or a function to be run by
+    /// every worker thread.
+    ///
+    /// See `Actor` for details. Actors can also be added to a running runtime
+    /// with `Handle::register_actor`.
+    pub fn actor(mut self, actor: impl Into<Actor>) -> Self {
+        self.actors.push(actor.into());
+        self
+    }
+
+    /// Set the function that creates the parker of each worker thread, of each
+    /// thread of blocking jobs, and of each thread that blocks on the runtime
+    /// with `Runtime::block_on`.
+    ///
+    /// The function is called on the thread to be parked. By default, the
+    /// threads are parked with `ThreadParker` if std is available, or with
+    /// `SpinParker` otherwise, which keeps the idle threads spinning. Without
+    /// std, e.g., in an enclave, a parker that sleeps through OCalls should be
+    /// given instead.
+    pub fn parker<P: Parker + 'static>(
+        mut self,
+        new_parker: impl Fn() -> P + Send + Sync + 'static,
+    ) -> Self {
+        self.parker = Some(Arc::new(move || Box::new(new_parker()) as Box<dyn Parker>));
+        self
+    }
+
+    /// Set the function that spawns a worker thread or a blocking thread with
+    /// the given name.
+    ///
+    /// By default, the threads are spawned with `std::thread`. Without std, a
+    /// thread spawner must be given, e.g., one that uses the threads of an SGX
+    /// enclave. The function may be called from any thread after the runtime is
+    /// built, since the blocking threads are spawned on demand.
+    pub fn thread_spawner(
+        mut self,
+        spawn_thread: impl Fn(String, Box<dyn FnOnce() + Send>) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.thread_spawner = Some(Arc::new(spawn_thread));
+        self
+    }
+
+    /// Pin the worker thread of index `i` to `cpus[i]`, where the number of the
+    /// CPUs must be equal to the number of worker threads.
+    ///
+    /// The mapping can be queried with `Handle::worker_cpu`, e.g., to place the
+    /// kernel thread of an io_uring with `setup_sqpoll_cpu` next to the worker
+    /// threads. By default, the worker threads are not pinned.
+    pub fn worker_cpus(mut self, cpus: impl Into<Vec<u32>>) -> Self {
+        self.worker_cpus = Some(cpus.into());
+        self
+    }
+
+    /// Set the function that pins the current thread to the given CPU.
+    ///
+    /// The function is called on each worker thread before it runs any tasks,
+    /// if `worker_cpus` is given. By default, the threads are pinned with
+    /// `sched_setaffinity` if std is available on Linux. Without std, a CPU
+    /// pinner must be given, e.g., one that makes an OCall under SGX.
+    pub fn cpu_pinner(
+        mut self,
+        pin_cpu: impl Fn(u32) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.cpu_pinner = Some(Arc::new(pin_cpu));
+        self
+    }
+
+    /// Set the way to catch the panics of tasks and blocking jobs, so that a
+    /// panic only fails its own task or job.
+    ///
+    /// By default, the panics are caught with `std::panic::catch_unwind` if
+    /// std is available. Without std, a panic takes down its thread unless an
+    /// unwinder is given, e.g., one that uses `sgx_tstd::panic` in an enclave.
+    pub fn unwinder(mut self, unwinder: impl Unwinder + 'static) -> Self {
+        self.unwinder = Some(Arc::new(unwinder));
+        self
+    }
+
+    /// Set whether to measure the time spent in polling tasks and running
+    /// actors, which is on by default.
+    ///
+    /// Each measurement reads the clock twice, which is two OCalls with a clock
+    /// that reads the time of the host. Without the timing, the times in
+    /// `WorkerMetrics` stay zero while the other metrics are still collected.
+    pub fn timing_metrics(mut self, is_enabled: bool) -> Self {
+        self.timing_metrics = is_enabled;
+        self
+    }
+
+    /// Make the runtime deterministic with the given seed, for tests.
+    ///
+    /// A deterministic runtime has exactly one worker thread, which picks the
+    /// next task to run at random with the seed. See the `sim` module.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Create the runtime and start its worker threads.
+    pub fn build(mut self) -> Result<Runtime> {
+        let spawn_thread = match self.thread_spawner.take() {
+            Some(thread_spawner) => thread_spawner,
+            None => default_thread_spawner()?,
+        };
+        self.thread_spawner = Some(spawn_thread.clone());
+        let worker_threads = self.worker_threads;
+        let thread_name = self.thread_name.clone();
+        let unwinder = self.unwinder.clone();
+        let executor = self.build_executor()?;
+
+        let runtime = Runtime {
+            handle: Handle::new(executor.clone()),
+            num_workers: Arc::new(AtomicU32::new(0)),
+        };
+
+        for thread_idx in 0..worker_threads {
+            let thread_name = format!("{}-{}", thread_name, thread_idx);
+            let executor = executor.clone();
+            let unwinder = unwinder.clone();
+            let worker_guard = WorkerGuard::new(&runtime.num_workers);
+            let worker = Box::new(move || {
+                crate::executor::with_unwinder(unwinder.as_ref(), || {
+                    executor.run_tasks(executor.new_parker())
+                });
+                drop(worker_guard);
+            });
+            // If this fails, dropping the runtime stops the threads spawned so far
+            spawn_thread(thread_name, worker)?;
+        }
+        Ok(runtime)
+    }
+
+    /// Create the executor of a runtime without starting its worker threads.
+    pub(crate) fn build_executor(self) -> Result<Arc<Executor>> {
+        let spawn_thread = match self.thread_spawner {
+            Some(thread_spawner) => thread_spawner,
+            None => default_thread_spawner()?,
+        };
+        let new_parker = match self.parker {
+            Some(parker) => parker,
+            None => Arc::new(crate::executor::default_parker),
+        };
+        let worker_cpus = match self.worker_cpus {
+            Some(cpus) => {
+                let pin_cpu = match self.cpu_pinner {
+                    Some(cpu_pinner) => cpu_pinner,
+                    None => crate::executor::default_cpu_pinner()?,
+                };
+                Some(WorkerCpus::new(cpus, pin_cpu))
+            }
+            None => None,
+        };
+
+        if self.max_blocking_threads == 0 {
+            return Err("invalid argument");
+        }
+        let blocking_pool = BlockingPool::new(
+            self.max_blocking_threads,
+            self.thread_name.clone(),
+            spawn_thread.clone(),
+            new_parker.clone(),
+            self.unwinder.clone(),
+        );
+
+        let executor = Arc::new(Executor::new(ExecutorConfig {
+            parallelism: self.worker_threads,
+            max_queued_tasks: self.max_queued_tasks,
+            actors: self.actors,
+            blocking_pool,
+            simulation: self.seed.map(Simulation::new),
+            worker_cpus,
+            new_parker,
+            timing_metrics: self.timing_metrics,
+        })?);
+        Ok(executor)
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+fn default_thread_spawner() -> Result<ThreadSpawner> {
+    Ok(Arc::new(|thread_name, worker| {
+        std::thread::Builder::new()
+            .name(thread_name)
+            .spawn(worker)
+            .map(|_| ())
+            .map_err(|_| "failed to spawn a worker thread")
+    }))
+}
+
+#[cfg(not(any(test, feature = "std")))]
+fn default_thread_spawner() -> Result<ThreadSpawner> {
+    Err("a thread spawner is required without std")
+}
+
+/// Counts a worker thread as running until it is dropped, even by a panic.
+struct WorkerGuard {
+    num_workers: Arc<AtomicU32>,
+}
+
+impl WorkerGuard {
+    pub fn new(num_workers: &Arc<AtomicU32>) -> Self {
+        num_workers.fetch_add(1, Ordering::AcqRel);
+        let num_workers = num_workers.clone();
+        Self { num_workers }
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        self.num_workers.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,294 @@
+//! Runtimes, each of which owns an executor and its worker threads.
+//!
+//! A runtime is created with a `Builder`. Several runtimes may coexist in one
+//! process, each serving its own tasks. A task spawned with `task::spawn` runs
+//! on the same runtime as the spawning task.
+
+use core::time::Duration;
+
+use crate::executor::Executor;
+use crate::prelude::*;
+use crate::sched::{Priority, SchedInfo};
+use crate::task::{JoinHandle, Task, TaskId, TaskInfo};
+
+pub use self::builder::Builder;
+pub use crate::executor::{Actor, ActorId, RuntimeMetrics, WorkerMetrics};
+
+mod builder;
+
+/// A runtime, whose worker threads run until the runtime is dropped.
+pub struct Runtime {
+    handle: Handle,
+    num_workers: Arc<AtomicU32>,
+}
+
+impl Runtime {
+    /// Create a runtime with the default configuration.
+    pub fn new() -> Result<Self> {
+        Builder::new().build()
+    }
+
+    /// Returns a handle to the runtime, which can be cloned and sent to other threads.
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Spawn a new task on the runtime.
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        self.handle.spawn(future)
+    }
+
+    /// Run a future on the runtime and block the current thread until it completes.
+    pub fn block_on<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> T {
+        self.handle.block_on(future)
+    }
+
+    /// Shut down the runtime gracefully.
+    ///
+    /// The runtime stops accepting new tasks at once, whose join handles resolve
+    /// to `JoinError::Shutdown`. The in-flight tasks are given the timeout to
+    /// finish, after which the remaining ones are cancelled with the same error.
+    /// Then the worker threads exit, the blocking jobs that have been submitted
+    /// run to completion, and the actors are run until none of them
+    /// has any pending work, e.g., io_uring callbacks.
+    ///
+    /// Returns an error if any tasks are cancelled. If called from a worker
+    /// thread of the runtime, which cannot wait for itself, the runtime is only
+    /// told to stop as if the timeout were zero, and an error is returned.
+    ///
+    /// # Panics
+    ///
+    /// With a non-zero timeout, this method panics if no clock has been set.
+    pub fn shutdown_graceful(mut self, timeout: Duration) -> Result<()> {
+        self.do_shutdown(timeout)
+    }
+
+    fn do_shutdown(&mut self, timeout: Duration) -> Result<()> {
+        let executor = &self.handle.executor;
+        if executor.is_shutdown() {
+            return Ok(());
+        }
+        executor.close();
+
+        // Waiting on a worker thread would deadlock, since that thread is the
+        // one to run the remaining tasks and then to exit
+        if executor.current_thread_id().is_some() {
+            executor.cancel_tasks();
+            executor.shutdown();
+            return Err("a runtime cannot wait for its own worker threads");
+        }
+
+        if timeout > Duration::from_secs(0) {
+            let deadline = crate::time::now() + timeout;
+            while executor.num_tasks() > 0 && crate::time::now() < deadline {
+                yield_thread();
+            }
+        }
+        let has_finished = executor.num_tasks() == 0;
+        if !has_finished {
+            executor.cancel_tasks();
+            while executor.num_tasks() > 0 {
+                yield_thread();
+            }
+        }
+
+        // Shutdown the executor and wait for the worker threads to exit, as
+        // well as the threads of blocking jobs once the jobs are done
+        executor.shutdown();
+        while self.num_workers.load(Ordering::Acquire) > 0 {
+            yield_thread();
+        }
+        while executor.blocking_pool().num_threads() > 0 {
+            yield_thread();
+        }
+        while executor.run_actors() {}
+
+        if has_finished {
+            Ok(())
+        } else {
+            Err("some tasks are cancelled by shutdown")
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // Any tasks that are still in flight are cancelled
+        let _ = self.do_shutdown(Duration::from_secs(0));
+    }
+}
+
+/// A handle to a runtime.
+#[derive(Clone)]
+pub struct Handle {
+    executor: Arc<Executor>,
+}
+
+impl Handle {
+    /// Returns the handle to the runtime of the current worker thread.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if not called from a worker thread of a runtime.
+    pub fn current() -> Self {
+        match Self::try_current() {
+            Some(handle) => handle,
+            None => panic!("not called from a worker thread of a runtime"),
+        }
+    }
+
+    /// Returns the handle to the runtime of the current worker thread, if any.
+    pub fn try_current() -> Option<Self> {
+        crate::executor::current().map(Self::new)
+    }
+
+    pub(crate) fn new(executor: Arc<Executor>) -> Self {
+        Self { executor }
+    }
+
+    pub(crate) fn executor(&self) -> &Arc<Executor> {
+        &self.executor
+    }
+
+    /// Returns the number of worker threads.
+    pub fn parallelism(&self) -> u32 {
+        self.executor.parallelism()
+    }
+
+    /// Returns the seed of the runtime if it is deterministic.
+    pub fn seed(&self) -> Option<u64> {
+        self.executor.simulation().map(|sim| sim.seed())
+    }
+
+    /// Returns the CPU that the worker thread of the given index is pinned to,
+    /// if the runtime is built with `Builder::worker_cpus`.
+    pub fn worker_cpu(&self, thread_id: u32) -> Option<u32> {
+        self.executor.worker_cpu(thread_id as usize)
+    }
+
+    /// Spawn a new task on the runtime.
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        self.spawn_with_priority(Priority::default(), future)
+    }
+
+    /// Spawn a new task with the given priority on the runtime.
+    pub fn spawn_with_priority<T: Send + 'static>(
+        &self,
+        priority: Priority,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> JoinHandle<T> {
+        let sched_info = SchedInfo::new(self.parallelism(), priority);
+        let (task, join_handle) = crate::task::new_task(future, sched_info, None, &self.executor);
+        self.executor.accept_task(task);
+        join_handle
+    }
+
+    /// Run a blocking function on a thread dedicated to blocking jobs.
+    ///
+    /// See `task::spawn_blocking`.
+    pub fn spawn_blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> JoinHandle<T> {
+        let (job, join_handle) = crate::task::new_blocking_job(f);
+        // A refused job is dropped, which joins it as cancelled
+        let _ = self.executor.blocking_pool().submit(job);
+        join_handle
+    }
+
+    /// Spawn a new task on the runtime unless the executor is overloaded.
+    ///
+    /// See `task::try_spawn`.
+    pub fn try_spawn<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> Result<JoinHandle<T>> {
+        let sched_info = SchedInfo::new(self.parallelism(), Priority::default());
+        let (task, join_handle) = crate::task::new_task(future, sched_info, None, &self.executor);
+        self.executor.try_accept_task(task)?;
+        Ok(join_handle)
+    }
+
+    /// Run a future on the runtime and block the current thread until it completes.
+    ///
+    /// The current thread is parked while waiting. If it is a worker thread of
+    /// the runtime, it keeps running other tasks instead, so blocking on a future
+    /// inside a task does not deadlock.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the future panics or if the runtime is shut down
+    /// before the future completes.
+    pub fn block_on<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> T {
+        let join_handle = self.spawn(future);
+        match self.executor.block_on(join_handle) {
+            Ok(output) => output,
+            Err(e) => panic!("failed to block on a future: {}", e),
+        }
+    }
+
+    /// Returns the task of the given ID if it is spawned on the runtime and has
+    /// not finished yet.
+    pub(crate) fn find_task(&self, tid: TaskId) -> Option<Arc<Task>> {
+        self.executor.find_task(tid)
+    }
+
+    /// Take a snapshot of the metrics of the runtime.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.executor.metrics()
+    }
+
+    /// List the live tasks of the runtime, in the order of their IDs.
+    pub fn dump_tasks(&self) -> Vec<TaskInfo> {
+        self.executor
+            .live_tasks()
+            .iter()
+            .map(|task| TaskInfo::new(task))
+            .collect()
+    }
+
+    /// Add an actor to the runtime.
+    ///
+    /// An error is returned if the actor is bound to a thread that does not
+    /// exist or if its interval is zero.
+    pub fn register_actor(&self, actor: impl Into<Actor>) -> Result<ActorId> {
+        self.executor.register_actor(actor.into())
+    }
+
+    /// Remove an actor from the runtime.
+    ///
+    /// If the actor is being run by a worker thread, that run is not interrupted.
+    pub fn unregister_actor(&self, id: ActorId) -> Result<()> {
+        self.executor.unregister_actor(id)
+    }
+
+    /// Wake up all parked worker threads.
+    ///
+    /// An event source that an actor polls (e.g., io_uring completions) may call
+    /// this method to have the actor run sooner.
+    pub fn unpark_all(&self) {
+        self.executor.unpark_all()
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+fn yield_thread() {
+    std::thread::yield_now();
+}
+
+#[cfg(not(any(test, feature = "std")))]
+fn yield_thread() {
+    core::sync::atomic::spin_loop_hint();
+}
@@ -1,6 +1,8 @@
 use bit_vec::BitVec;
 
-use crate::executor::EXECUTOR;
+use crate::prelude::*;
+use crate::runtime::Handle;
+use crate::task::TaskId;
 
 /// The set of executor threads that a task can be scheduled to.
 #[derive(Debug, Clone, PartialEq)]
@@ -9,20 +11,43 @@ pub struct Affinity {
 }
 
 impl Affinity {
-    /// The max number of executor threads in a set.
+    /// The max number of executor threads in a set, which is the number of
+    /// worker threads of the current runtime.
+    ///
+    /// If not called from a worker thread, this is the parallelism of the
+    /// global executor (see `executor::set_parallelism`). Use `full` or `empty`
+    /// to create a set for a runtime from elsewhere.
     pub fn max_threads() -> usize {
-        EXECUTOR.parallelism() as usize
+        let parallelism = match Handle::try_current() {
+            Some(handle) => handle.parallelism(),
+            None => crate::executor::global_parallelism(),
+        };
+        parallelism as usize
     }
 
-    /// A full set of executor threads.
+    /// A full set of executor threads. See `max_threads`.
     pub fn new_full() -> Self {
-        let bits = BitVec::from_elem(Self::max_threads(), true);
-        Self { bits }
+        Self::full(Self::max_threads())
     }
 
-    /// A empty set of executor threads.
+    /// A empty set of executor threads. See `max_threads`.
     pub fn new_empty() -> Self {
-        let bits = BitVec::from_elem(Self::max_threads(), false);
+        Self::empty(Self::max_threads())
+    }
+
+    /// A full set of the given number of executor threads, e.g., the
+    /// parallelism of a `Handle`.
+    pub fn full(max_threads: usize) -> Self {
+        Self::from_elem(max_threads, true)
+    }
+
+    /// A empty set of the given number of executor threads.
+    pub fn empty(max_threads: usize) -> Self {
+        Self::from_elem(max_threads, false)
+    }
+
+    fn from_elem(max_threads: usize, is_set: bool) -> Self {
+        let bits = BitVec::from_elem(max_threads, is_set);
         Self { bits }
     }
 
@@ -42,17 +67,84 @@ impl Affinity {
     }
 
     /// Set whether the i-th thread is in the set.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `i` is not less than the max number of threads.
     pub fn set(&mut self, i: usize, b: bool) {
         self.bits.set(i, b);
     }
 
-    /// Get whether the i-th thread is in the set.
+    /// Get whether the i-th thread is in the set, which is false if `i` is out of range.
     pub fn get(&self, i: usize) -> bool {
-        self.bits.get(i).unwrap()
+        self.bits.get(i).unwrap_or(false)
     }
 
     /// Returns an iterator that allows accessing the underlying bits.
     pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
         self.bits.iter()
     }
+
+    /// Check that the set is a valid affinity for a runtime with the given
+    /// number of worker threads.
+    pub(crate) fn validate(&self, parallelism: u32) -> Result<()> {
+        if self.bits.len() != parallelism as usize {
+            return Err("the affinity does not match the number of worker threads");
+        }
+        if self.is_empty() {
+            return Err("the affinity is empty");
+        }
+        Ok(())
+    }
+}
+
+/// Returns the index of the current worker thread, which is what the bits of
+/// an `Affinity` refer to, or `None` if not called from a worker thread.
+pub fn current_thread_id() -> Option<usize> {
+    crate::executor::current_thread_id()
+}
+
+/// Set the affinity of the current task.
+///
+/// If the current thread is not in the new affinity, the task is migrated to
+/// one that is before this function returns. An error is returned if the
+/// affinity is empty or does not match the number of worker threads, or if the
+/// task is a local one, in which case the affinity is unchanged.
+///
+/// # Panics
+///
+/// This function panics if not called from a task.
+pub async fn set_affinity(affinity: &Affinity) -> Result<()> {
+    let current = crate::task::current();
+    affinity.validate(Handle::current().parallelism())?;
+    if current.sched_info().local_thread_id().is_some() {
+        return Err("the affinity of a local task cannot be changed");
+    }
+    *current.sched_info().affinity().write() = affinity.clone();
+
+    // Rescheduling the task moves it to one of the threads in its affinity
+    while !affinity.get(current_thread_id().unwrap()) {
+        crate::sched::yield_().await;
+    }
+    Ok(())
+}
+
+/// Set the affinity of a task of the current runtime.
+///
+/// The new affinity takes effect at the next scheduling of the task. An error
+/// is returned if the affinity is invalid (see `set_affinity`) or if the task
+/// has finished.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime.
+pub fn set_task_affinity(tid: TaskId, affinity: &Affinity) -> Result<()> {
+    let handle = Handle::current();
+    affinity.validate(handle.parallelism())?;
+    let task = handle.find_task(tid).ok_or("the task is not found")?;
+    if task.sched_info().local_thread_id().is_some() {
+        return Err("the affinity of a local task cannot be changed");
+    }
+    *task.sched_info().affinity().write() = affinity.clone();
+    Ok(())
 }
@@ -0,0 +1,66 @@
+//! Cooperative scheduling budget.
+//!
+//! A future that is always ready (e.g., a read from a socket whose buffer is
+//! never drained) never returns `Poll::Pending` by itself, so it would occupy a
+//! worker thread forever. To prevent this, each poll of a task is given a budget
+//! of operations. The leaf futures (e.g., socket operations) consume the budget
+//! and force the task to yield once the budget is exhausted.
+
+use core::cell::Cell;
+
+use futures::future::poll_fn;
+
+use crate::prelude::*;
+
+/// The number of operations that a task may perform in one poll.
+pub const INITIAL_BUDGET: u32 = 128;
+
+// The budget left for the current poll, or `None` if not in a poll of a task
+#[thread_local]
+static BUDGET: Cell<Option<u32>> = Cell::new(None);
+
+/// Run a poll of a task with a fresh budget.
+///
+/// The previous budget is restored afterwards, which matters when a task is
+/// polled inside another one, e.g., by `block_on` on a worker thread.
+pub(crate) fn with_budget<R>(f: impl FnOnce() -> R) -> R {
+    struct ResetGuard(Option<u32>);
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            BUDGET.set(self.0);
+        }
+    }
+
+    let _guard = ResetGuard(BUDGET.replace(Some(INITIAL_BUDGET)));
+    f()
+}
+
+/// Consume one unit of the budget of the current task, or arrange for the task
+/// to be woken up again if the budget has been exhausted.
+///
+/// This always succeeds if not called from a task.
+pub fn poll_consume_budget(cx: &mut Context<'_>) -> Poll<()> {
+    match BUDGET.get() {
+        None => Poll::Ready(()),
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(budget) => {
+            BUDGET.set(Some(budget - 1));
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Consume one unit of the budget of the current task, yielding to other tasks
+/// if the budget has been exhausted.
+pub async fn consume_budget() {
+    poll_fn(poll_consume_budget).await
+}
+
+/// Returns whether the budget of the current task has been exhausted.
+pub fn is_budget_exhausted() -> bool {
+    BUDGET.get() == Some(0)
+}
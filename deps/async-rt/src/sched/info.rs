@@ -1,29 +1,51 @@
+use core::sync::atomic::AtomicU8;
+
 use spin::rw_lock::RwLock;
 
-use crate::executor::EXECUTOR;
 use crate::prelude::*;
-use crate::sched::Affinity;
+use crate::sched::{Affinity, Priority};
 
 /// A per-task scheduling-related info.
 pub struct SchedInfo {
     last_thread_id: AtomicU32,
     affinity: RwLock<Affinity>,
+    priority: AtomicU8,
+    // The only thread that a local task can run on
+    local_thread_id: Option<u32>,
 }
 
 impl SchedInfo {
-    pub fn new() -> Self {
+    pub fn new(parallelism: u32, priority: Priority) -> Self {
         static LAST_THREAD_ID: AtomicU32 = AtomicU32::new(0);
 
         let last_thread_id = {
-            let last_thread_id =
-                LAST_THREAD_ID.fetch_add(1, Ordering::Relaxed) % EXECUTOR.parallelism();
+            let last_thread_id = LAST_THREAD_ID.fetch_add(1, Ordering::Relaxed) % parallelism;
             AtomicU32::new(last_thread_id)
         };
-        let affinity = RwLock::new(Affinity::new_full());
+        let affinity = RwLock::new(Affinity::full(parallelism as usize));
+        let priority = AtomicU8::new(priority as u8);
 
         Self {
             last_thread_id,
             affinity,
+            priority,
+            local_thread_id: None,
+        }
+    }
+
+    /// Create the info of a local task, which is bound to the given thread for good.
+    pub fn new_local(parallelism: u32, thread_id: u32) -> Self {
+        let affinity = {
+            let mut affinity = Affinity::empty(parallelism as usize);
+            affinity.set(thread_id as usize, true);
+            affinity
+        };
+
+        Self {
+            last_thread_id: AtomicU32::new(thread_id),
+            affinity: RwLock::new(affinity),
+            priority: AtomicU8::new(Priority::default() as u8),
+            local_thread_id: Some(thread_id),
         }
     }
 
@@ -38,4 +60,17 @@ impl SchedInfo {
     pub fn affinity(&self) -> &RwLock<Affinity> {
         &self.affinity
     }
+
+    /// Returns the thread of a local task, or `None` if the task is not local.
+    pub fn local_thread_id(&self) -> Option<u32> {
+        self.local_thread_id
+    }
+
+    pub fn priority(&self) -> Priority {
+        Priority::from_u8(self.priority.load(Ordering::Relaxed))
+    }
+
+    pub fn set_priority(&self, priority: Priority) {
+        self.priority.store(priority as u8, Ordering::Relaxed);
+    }
 }
@@ -1,7 +1,11 @@
 mod affinity;
+pub mod coop;
 mod info;
+mod priority;
 mod yield_;
 
-pub use self::affinity::Affinity;
+pub use self::affinity::{current_thread_id, set_affinity, set_task_affinity, Affinity};
+pub use self::coop::consume_budget;
 pub use self::info::SchedInfo;
+pub use self::priority::{priority, set_priority, Priority};
 pub use self::yield_::yield_;
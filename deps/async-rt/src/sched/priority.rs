@@ -0,0 +1,61 @@
+/// The scheduling priority of a task.
+///
+/// Each executor thread keeps a run queue per priority. A thread prefers the
+/// tasks of higher priorities, but still visits the lower ones once in a while,
+/// so that a busy high-priority task cannot starve the others.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Priority {
+    Low = 0,
+    Normal = 1,
+    High = 2,
+}
+
+impl Priority {
+    /// The number of priorities.
+    pub(crate) const COUNT: usize = 3;
+
+    /// All priorities, from the highest to the lowest.
+    pub(crate) const ALL: [Priority; Priority::COUNT] =
+        [Priority::High, Priority::Normal, Priority::Low];
+
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Priority::Low,
+            1 => Priority::Normal,
+            2 => Priority::High,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the index of the priority, which is smaller for a higher priority.
+    pub(crate) fn index(self) -> usize {
+        Priority::High as usize - self as usize
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Set the priority of the current task.
+///
+/// The new priority takes effect after the next scheduling.
+///
+/// # Panics
+///
+/// This function panics if not called from a task.
+pub fn set_priority(priority: Priority) {
+    crate::task::current().sched_info().set_priority(priority);
+}
+
+/// Returns the priority of the current task.
+///
+/// # Panics
+///
+/// This function panics if not called from a task.
+pub fn priority() -> Priority {
+    crate::task::current().sched_info().priority()
+}
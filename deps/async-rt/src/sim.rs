@@ -0,0 +1,104 @@
+//! Deterministic simulation for tests.
+//!
+//! A runtime built with `Builder::deterministic` has a single worker thread,
+//! which picks the next task to run at random from all runnable tasks, using an
+//! RNG seeded by the given seed. So a race between tasks that shows up with one
+//! seed shows up again with the same seed, and can be replayed and debugged.
+//!
+//! Other sources of events, e.g., the completions of an io_uring, can use the
+//! same RNG through `random_index` to decide the order of the events that are
+//! ready at the same time:
+//!
+//! ```ignore
+//! let rt = Builder::new()
+//!     .deterministic(seed)
+//!     .actor(move || ring.trigger_callbacks_with(async_rt::sim::random_index) > 0)
+//!     .build()?;
+//! ```
+//!
+//! The simulation does not control when the events happen in the first place.
+//! The timers and the blocking jobs follow the real time, unless a fake clock is
+//! installed with `time::set_clock`.
+
+use crate::prelude::*;
+use crate::task::Task;
+
+/// Returns a random index below `len` drawn from the RNG of the deterministic
+/// runtime of the current thread.
+///
+/// If not called from a worker thread of a deterministic runtime, this always
+/// returns 0, so that the events are handled in their natural order.
+///
+/// # Panics
+///
+/// This function panics if `len` is zero.
+pub fn random_index(len: usize) -> usize {
+    assert!(len > 0);
+    crate::executor::current()
+        .and_then(|executor| executor.simulation().map(|sim| sim.random_index(len)))
+        .unwrap_or(0)
+}
+
+/// The state of a deterministic runtime.
+pub(crate) struct Simulation {
+    seed: u64,
+    rng: Mutex<SimRng>,
+    // The runnable tasks, which take the place of the run queues
+    tasks: Mutex<Vec<Arc<Task>>>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Mutex::new(SimRng::new(seed)),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn random_index(&self, len: usize) -> usize {
+        (self.rng.lock().next_u64() % len as u64) as usize
+    }
+
+    pub fn push_task(&self, task: Arc<Task>) {
+        self.tasks.lock().push(task);
+    }
+
+    /// Remove a task chosen at random from the runnable ones.
+    pub fn pop_task(&self) -> Option<Arc<Task>> {
+        let mut tasks = self.tasks.lock();
+        if tasks.is_empty() {
+            return None;
+        }
+        let index = self.random_index(tasks.len());
+        Some(tasks.swap_remove(index))
+    }
+
+    pub fn has_tasks(&self) -> bool {
+        !self.tasks.lock().is_empty()
+    }
+}
+
+/// A small PRNG (SplitMix64), which is good enough for shuffling and is the
+/// same on every platform.
+struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,67 @@
+use core::task::Waker;
+
+use futures::future::poll_fn;
+
+use crate::prelude::*;
+
+/// A barrier that lets a number of tasks wait until all of them reach it.
+///
+/// The barrier can be reused once all the tasks have passed it. Note that a
+/// task that stops waiting before the barrier is passed, e.g., because it is
+/// aborted, is still counted as having reached the barrier.
+pub struct Barrier {
+    num_tasks: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    num_arrived: usize,
+    generation: u64,
+    wakers: Vec<Waker>,
+}
+
+impl Barrier {
+    /// Create a barrier for the given number of tasks, which must be non-zero.
+    pub fn new(num_tasks: usize) -> Self {
+        assert!(num_tasks > 0);
+        let state = Mutex::new(State {
+            num_arrived: 0,
+            generation: 0,
+            wakers: Vec::new(),
+        });
+        Self { num_tasks, state }
+    }
+
+    /// Wait until all tasks have reached the barrier.
+    ///
+    /// Returns true for exactly one of the tasks, which is called the leader.
+    pub async fn wait(&self) -> bool {
+        let generation = {
+            let mut state = self.state.lock();
+            state.num_arrived += 1;
+            if state.num_arrived < self.num_tasks {
+                state.generation
+            } else {
+                state.num_arrived = 0;
+                state.generation += 1;
+                let wakers = core::mem::replace(&mut state.wakers, Vec::new());
+                drop(state);
+
+                wakers.into_iter().for_each(|waker| waker.wake());
+                return true;
+            }
+        };
+
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            if state.generation != generation {
+                return Poll::Ready(false);
+            }
+            if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
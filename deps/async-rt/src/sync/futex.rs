@@ -0,0 +1,319 @@
+//! Futexes, with which a LibOS can emulate the futex system calls.
+//!
+//! A futex is identified by the address of an `AtomicI32`. The waiters of the
+//! futexes are kept in a hash table, which is sharded by the addresses of the
+//! futexes so that the futexes that are unrelated rarely contend for a lock.
+
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::sync::atomic::{AtomicI32, AtomicUsize};
+use core::task::Waker;
+use core::time::Duration;
+
+use crate::prelude::*;
+
+/// The bitset that matches any waiters, which is used by `wait` and `wake`.
+pub const FUTEX_BITSET_MATCH_ANY: u32 = 0xFFFF_FFFF;
+
+/// The error of waiting on a futex.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FutexError {
+    /// The value of the futex is not the expected one, i.e., EAGAIN.
+    WouldBlock,
+    /// The waiter is not woken up before the timeout, i.e., ETIMEDOUT.
+    TimedOut,
+    /// The bitset is zero, i.e., EINVAL.
+    InvalidArgument,
+}
+
+impl fmt::Display for FutexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FutexError::WouldBlock => write!(f, "futex value mismatched"),
+            FutexError::TimedOut => write!(f, "futex wait timed out"),
+            FutexError::InvalidArgument => write!(f, "invalid argument"),
+        }
+    }
+}
+
+/// Wait on a futex until it is woken up, if its value is the expected one.
+///
+/// This is FUTEX_WAIT, whose timeout is relative.
+pub async fn wait(
+    futex: &AtomicI32,
+    expected: i32,
+    timeout: Option<Duration>,
+) -> core::result::Result<(), FutexError> {
+    let deadline = timeout.map(|timeout| crate::time::now() + timeout);
+    wait_bitset(futex, expected, deadline, FUTEX_BITSET_MATCH_ANY).await
+}
+
+/// Wait on a futex with a bitset, which restricts the wakers that can wake it up.
+///
+/// This is FUTEX_WAIT_BITSET, whose timeout is an absolute deadline, i.e., a
+/// point of time of the clock (see `time::now`).
+///
+/// A waiter that is woken up is never reported as timed out, even if the
+/// wakeup races with the deadline.
+pub async fn wait_bitset(
+    futex: &AtomicI32,
+    expected: i32,
+    deadline: Option<Duration>,
+    bitset: u32,
+) -> core::result::Result<(), FutexError> {
+    if bitset == 0 {
+        return Err(FutexError::InvalidArgument);
+    }
+
+    let addr = futex_addr(futex);
+    let waiter = Arc::new(Waiter {
+        addr: AtomicUsize::new(addr),
+        bitset,
+        is_woken: AtomicBool::new(false),
+        waker: Mutex::new(None),
+    });
+    {
+        // Checking the value with the lock being held ensures that any wakeup
+        // after the check is not missed
+        let mut bucket = FUTEX_TABLE.bucket(addr).lock();
+        if futex.load(Ordering::SeqCst) != expected {
+            return Err(FutexError::WouldBlock);
+        }
+        bucket.push_back(waiter.clone());
+    }
+
+    let mut wait = Wait { waiter };
+    let mut sleep = match deadline {
+        None => {
+            wait.await;
+            return Ok(());
+        }
+        Some(deadline) => crate::time::sleep_until(deadline),
+    };
+    futures::future::poll_fn(|cx| {
+        if Pin::new(&mut wait).poll(cx).is_ready() {
+            return Poll::Ready(Ok(()));
+        }
+        if Pin::new(&mut sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        // A waker may have dequeued the waiter but not set it woken yet
+        if wait.cancel() {
+            Poll::Ready(Err(FutexError::TimedOut))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    })
+    .await
+}
+
+/// Wake up at most `max_count` waiters of a futex. Returns the number of
+/// waiters woken up.
+///
+/// This is FUTEX_WAKE.
+pub fn wake(futex: &AtomicI32, max_count: usize) -> usize {
+    wake_bitset(futex, max_count, FUTEX_BITSET_MATCH_ANY)
+}
+
+/// Wake up at most `max_count` waiters of a futex whose bitsets intersect with
+/// the given one. Returns the number of waiters woken up.
+///
+/// This is FUTEX_WAKE_BITSET.
+pub fn wake_bitset(futex: &AtomicI32, max_count: usize, bitset: u32) -> usize {
+    let addr = futex_addr(futex);
+    let mut woken_waiters = Vec::new();
+    {
+        let mut bucket = FUTEX_TABLE.bucket(addr).lock();
+        let mut idx = 0;
+        while idx < bucket.len() && woken_waiters.len() < max_count {
+            let waiter = &bucket[idx];
+            if waiter.addr() != addr || waiter.bitset & bitset == 0 {
+                idx += 1;
+                continue;
+            }
+            woken_waiters.push(bucket.remove(idx).unwrap());
+        }
+    }
+
+    let num_woken = woken_waiters.len();
+    woken_waiters.iter().for_each(|waiter| waiter.wake());
+    num_woken
+}
+
+/// Wake up at most `max_wake` waiters of a futex, and move at most
+/// `max_requeue` of the remaining waiters to another futex. Returns the number
+/// of waiters woken up or requeued.
+///
+/// This is FUTEX_REQUEUE.
+pub fn requeue(
+    futex: &AtomicI32,
+    max_wake: usize,
+    new_futex: &AtomicI32,
+    max_requeue: usize,
+) -> usize {
+    let addr = futex_addr(futex);
+    let new_addr = futex_addr(new_futex);
+
+    // Lock the buckets in a fixed order to avoid deadlocks. Both are held while
+    // the waiters are woken up and moved, so that no waiter can come in between.
+    let bucket_idx = FUTEX_TABLE.bucket_idx(addr);
+    let new_bucket_idx = FUTEX_TABLE.bucket_idx(new_addr);
+    let (mut bucket, mut new_bucket) = if bucket_idx < new_bucket_idx {
+        let bucket = FUTEX_TABLE.buckets[bucket_idx].lock();
+        let new_bucket = FUTEX_TABLE.buckets[new_bucket_idx].lock();
+        (bucket, Some(new_bucket))
+    } else if bucket_idx > new_bucket_idx {
+        let new_bucket = FUTEX_TABLE.buckets[new_bucket_idx].lock();
+        let bucket = FUTEX_TABLE.buckets[bucket_idx].lock();
+        (bucket, Some(new_bucket))
+    } else {
+        (FUTEX_TABLE.buckets[bucket_idx].lock(), None)
+    };
+
+    let mut woken_waiters = Vec::new();
+    let mut num_requeued = 0;
+    let mut idx = 0;
+    while idx < bucket.len() {
+        if bucket[idx].addr() != addr {
+            idx += 1;
+            continue;
+        }
+
+        if woken_waiters.len() < max_wake {
+            woken_waiters.push(bucket.remove(idx).unwrap());
+            continue;
+        }
+        if num_requeued == max_requeue || addr == new_addr {
+            break;
+        }
+
+        num_requeued += 1;
+        bucket[idx].addr.store(new_addr, Ordering::Relaxed);
+        match new_bucket.as_mut() {
+            Some(new_bucket) => {
+                let waiter = bucket.remove(idx).unwrap();
+                new_bucket.push_back(waiter);
+            }
+            // The waiter stays in the same bucket
+            None => idx += 1,
+        }
+    }
+    drop(new_bucket);
+    drop(bucket);
+
+    let num_woken = woken_waiters.len();
+    woken_waiters.iter().for_each(|waiter| waiter.wake());
+    num_woken + num_requeued
+}
+
+fn futex_addr(futex: &AtomicI32) -> usize {
+    futex as *const AtomicI32 as usize
+}
+
+lazy_static! {
+    static ref FUTEX_TABLE: FutexTable = FutexTable::new();
+}
+
+struct FutexTable {
+    buckets: Vec<Mutex<VecDeque<Arc<Waiter>>>>,
+}
+
+impl FutexTable {
+    /// The number of buckets, which must be a power of two.
+    const NUM_BUCKETS: usize = 64;
+
+    pub fn new() -> Self {
+        let buckets = (0..Self::NUM_BUCKETS)
+            .map(|_| Mutex::new(VecDeque::new()))
+            .collect();
+        Self { buckets }
+    }
+
+    pub fn bucket(&self, addr: usize) -> &Mutex<VecDeque<Arc<Waiter>>> {
+        &self.buckets[self.bucket_idx(addr)]
+    }
+
+    pub fn bucket_idx(&self, addr: usize) -> usize {
+        // Fibonacci hashing, which spreads the aligned addresses evenly
+        let hash = (addr as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        (hash >> 32) as usize & (Self::NUM_BUCKETS - 1)
+    }
+}
+
+struct Waiter {
+    // The address of the futex, which is changed when the waiter is requeued.
+    // It is only changed with the lock of its bucket being held.
+    addr: AtomicUsize,
+    bitset: u32,
+    is_woken: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Waiter {
+    pub fn addr(&self) -> usize {
+        self.addr.load(Ordering::Relaxed)
+    }
+
+    pub fn wake(&self) {
+        let waker = {
+            let mut waker = self.waker.lock();
+            self.is_woken.store(true, Ordering::Release);
+            waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub fn is_woken(&self) -> bool {
+        self.is_woken.load(Ordering::Acquire)
+    }
+}
+
+/// A future that waits until an enqueued waiter is woken up.
+///
+/// If dropped before that, e.g., when timed out, the waiter is dequeued.
+struct Wait {
+    waiter: Arc<Waiter>,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut waker = self.waiter.waker.lock();
+        if self.waiter.is_woken() {
+            return Poll::Ready(());
+        }
+        *waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Wait {
+    /// Dequeue the waiter. Returns false if it has been dequeued by a waker,
+    /// i.e., it is or is about to be woken up.
+    fn cancel(&self) -> bool {
+        loop {
+            if self.waiter.is_woken() {
+                return false;
+            }
+
+            let addr = self.waiter.addr();
+            let mut bucket = FUTEX_TABLE.bucket(addr).lock();
+            // The waiter may have been requeued before the bucket is locked
+            if self.waiter.addr() != addr {
+                continue;
+            }
+            let old_len = bucket.len();
+            bucket.retain(|waiter| !Arc::ptr_eq(waiter, &self.waiter));
+            return bucket.len() < old_len;
+        }
+    }
+}
+
+impl Drop for Wait {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,19 @@
+//! Synchronization primitives for tasks.
+//!
+//! Unlike spin locks, these primitives suspend the waiting tasks instead of
+//! the executor threads, so they can be held or waited on across await points.
+
+pub use self::barrier::Barrier;
+pub use self::mutex::{Mutex, MutexGuard};
+pub use self::notify::{Notified, Notify};
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::semaphore::{Acquire, Semaphore, SemaphorePermit};
+
+mod barrier;
+pub mod futex;
+mod mutex;
+mod notify;
+pub mod oneshot;
+mod rwlock;
+mod semaphore;
+pub mod watch;
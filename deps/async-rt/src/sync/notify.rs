@@ -0,0 +1,144 @@
+use alloc::collections::VecDeque;
+use core::task::Waker;
+
+use crate::prelude::*;
+
+/// Notify tasks of events.
+///
+/// A notification sent by `notify_one` while no task is waiting is stored, so
+/// that the next task to wait completes at once. At most one notification is
+/// stored at a time.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    has_permit: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+// The fields of a waiter are only accessed with the lock of the state being held
+struct Waiter {
+    notified: Mutex<Option<NotifyKind>>,
+    waker: Mutex<Option<Waker>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum NotifyKind {
+    One,
+    All,
+}
+
+impl Notify {
+    pub fn new() -> Self {
+        let state = Mutex::new(State {
+            has_permit: false,
+            waiters: VecDeque::new(),
+        });
+        Self { state }
+    }
+
+    /// Wait for a notification.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter: None,
+        }
+    }
+
+    /// Notify the first waiting task, or store a notification if there is none.
+    pub fn notify_one(&self) {
+        let mut state = self.state.lock();
+        let waiter = match state.waiters.pop_front() {
+            None => {
+                state.has_permit = true;
+                return;
+            }
+            Some(waiter) => waiter,
+        };
+        *waiter.notified.lock() = Some(NotifyKind::One);
+        let waker = waiter.waker.lock().take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Notify all the tasks that are waiting right now.
+    ///
+    /// No notification is stored for the tasks that wait later.
+    pub fn notify_waiters(&self) {
+        let mut wakers = Vec::new();
+        {
+            let mut state = self.state.lock();
+            for waiter in state.waiters.drain(..) {
+                *waiter.notified.lock() = Some(NotifyKind::All);
+                if let Some(waker) = waiter.waker.lock().take() {
+                    wakers.push(waker);
+                }
+            }
+        }
+        wakers.into_iter().for_each(|waker| waker.wake());
+    }
+}
+
+/// A future that waits for a notification.
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: Option<Arc<Waiter>>,
+}
+
+impl<'a> Future for Notified<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let self_ = self.get_mut();
+        let mut state = self_.notify.state.lock();
+
+        if self_.waiter.is_none() {
+            if state.has_permit {
+                state.has_permit = false;
+                return Poll::Ready(());
+            }
+
+            let waiter = Arc::new(Waiter {
+                notified: Mutex::new(None),
+                waker: Mutex::new(None),
+            });
+            state.waiters.push_back(waiter.clone());
+            self_.waiter = Some(waiter);
+        }
+
+        let waiter = self_.waiter.as_ref().unwrap();
+        if waiter.notified.lock().is_some() {
+            self_.waiter = None;
+            return Poll::Ready(());
+        }
+        *waiter.waker.lock() = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Notified<'a> {
+    fn drop(&mut self) {
+        let waiter = match self.waiter.take() {
+            None => return,
+            Some(waiter) => waiter,
+        };
+
+        let mut state = self.notify.state.lock();
+        let notified = *waiter.notified.lock();
+        match notified {
+            None => {
+                state.waiters.retain(|other| !Arc::ptr_eq(other, &waiter));
+            }
+            Some(NotifyKind::One) => {
+                // Pass on the notification that is never received
+                drop(state);
+                self.notify.notify_one();
+            }
+            Some(NotifyKind::All) => {}
+        }
+    }
+}
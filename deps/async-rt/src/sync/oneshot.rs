@@ -0,0 +1,104 @@
+//! A channel for sending a single value between tasks.
+
+use core::task::Waker;
+
+use crate::prelude::*;
+
+/// Create a oneshot channel.
+pub fn channel<T: Send>() -> (Sender<T>, Receiver<T>) {
+    let state = Arc::new(Mutex::new(State {
+        value: None,
+        is_sender_dropped: false,
+        is_receiver_dropped: false,
+        waker: None,
+    }));
+    let sender = Sender {
+        state: state.clone(),
+    };
+    let receiver = Receiver { state };
+    (sender, receiver)
+}
+
+struct State<T> {
+    value: Option<T>,
+    is_sender_dropped: bool,
+    is_receiver_dropped: bool,
+    waker: Option<Waker>,
+}
+
+/// The sending half of a oneshot channel.
+pub struct Sender<T: Send> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T: Send> Sender<T> {
+    /// Send a value, which is given back if the receiver has been dropped.
+    pub fn send(self, value: T) -> core::result::Result<(), T> {
+        let mut state = self.state.lock();
+        if state.is_receiver_dropped {
+            return Err(value);
+        }
+        // The receiver is woken up when the sender is dropped at the end
+        state.value = Some(value);
+        Ok(())
+    }
+
+    /// Returns whether the receiver has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().is_receiver_dropped
+    }
+}
+
+impl<T: Send> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.is_sender_dropped = true;
+        let waker = state.waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The receiving half of a oneshot channel, which is a future of the value.
+///
+/// An error is returned if the sender is dropped without sending a value.
+pub struct Receiver<T: Send> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T: Send> Receiver<T> {
+    /// Receive the value if it has been sent.
+    pub fn try_recv(&mut self) -> Result<Option<T>> {
+        let mut state = self.state.lock();
+        match state.value.take() {
+            Some(value) => Ok(Some(value)),
+            None if state.is_sender_dropped => Err("the sender has been dropped"),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: Send> Future for Receiver<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        if let Some(value) = state.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if state.is_sender_dropped {
+            return Poll::Ready(Err("the sender has been dropped"));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T: Send> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.state.lock().is_receiver_dropped = true;
+    }
+}
@@ -0,0 +1,186 @@
+use alloc::collections::VecDeque;
+use core::task::Waker;
+
+use crate::prelude::*;
+
+/// A counting semaphore.
+///
+/// The waiters are served in FIFO order. So a waiter that asks for many
+/// permits cannot be starved by the ones that ask for fewer permits.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+// The fields of a waiter are only accessed with the lock of the state being held
+struct Waiter {
+    num_permits: usize,
+    is_granted: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        let state = Mutex::new(State {
+            permits,
+            waiters: VecDeque::new(),
+        });
+        Self { state }
+    }
+
+    /// Returns the number of permits that can be acquired right now.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+
+    /// Acquire a permit, waiting until one is available.
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Acquire the given number of permits, waiting until they are available.
+    pub fn acquire_many(&self, num_permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            num_permits,
+            waiter: None,
+        }
+    }
+
+    /// Acquire a permit if one is available right now.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Acquire the given number of permits if they are available right now.
+    pub fn try_acquire_many(&self, num_permits: usize) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.state.lock();
+        // Never jump the queue
+        if !state.waiters.is_empty() || state.permits < num_permits {
+            return None;
+        }
+        state.permits -= num_permits;
+        Some(SemaphorePermit::new(self, num_permits))
+    }
+
+    /// Add permits to the semaphore, which may be granted to the waiters.
+    pub fn add_permits(&self, num_permits: usize) {
+        let mut wakers = Vec::new();
+        {
+            let mut state = self.state.lock();
+            let state = &mut *state;
+            state.permits += num_permits;
+            while let Some(waiter) = state.waiters.front() {
+                if waiter.num_permits > state.permits {
+                    break;
+                }
+                state.permits -= waiter.num_permits;
+                waiter.is_granted.store(true, Ordering::Relaxed);
+                if let Some(waker) = waiter.waker.lock().take() {
+                    wakers.push(waker);
+                }
+                state.waiters.pop_front();
+            }
+        }
+
+        // Wake up the waiters without holding the lock
+        wakers.into_iter().for_each(|waker| waker.wake());
+    }
+}
+
+/// A future that acquires permits from a semaphore.
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    num_permits: usize,
+    waiter: Option<Arc<Waiter>>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let self_ = self.get_mut();
+        let semaphore = self_.semaphore;
+        let mut state = semaphore.state.lock();
+
+        if self_.waiter.is_none() {
+            if state.waiters.is_empty() && state.permits >= self_.num_permits {
+                state.permits -= self_.num_permits;
+                return Poll::Ready(SemaphorePermit::new(semaphore, self_.num_permits));
+            }
+
+            let waiter = Arc::new(Waiter {
+                num_permits: self_.num_permits,
+                is_granted: AtomicBool::new(false),
+                waker: Mutex::new(None),
+            });
+            state.waiters.push_back(waiter.clone());
+            self_.waiter = Some(waiter);
+        }
+
+        let waiter = self_.waiter.as_ref().unwrap();
+        if waiter.is_granted.load(Ordering::Relaxed) {
+            self_.waiter = None;
+            return Poll::Ready(SemaphorePermit::new(semaphore, self_.num_permits));
+        }
+        *waiter.waker.lock() = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for Acquire<'a> {
+    fn drop(&mut self) {
+        let waiter = match self.waiter.take() {
+            None => return,
+            Some(waiter) => waiter,
+        };
+
+        let mut state = self.semaphore.state.lock();
+        if waiter.is_granted.load(Ordering::Relaxed) {
+            // The permits have been granted, but never taken
+            drop(state);
+            self.semaphore.add_permits(self.num_permits);
+        } else {
+            state.waiters.retain(|other| !Arc::ptr_eq(other, &waiter));
+            // The removed waiter may have been blocking the ones behind it
+            drop(state);
+            self.semaphore.add_permits(0);
+        }
+    }
+}
+
+/// The permits acquired from a semaphore, which are released when dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    num_permits: usize,
+}
+
+impl<'a> SemaphorePermit<'a> {
+    fn new(semaphore: &'a Semaphore, num_permits: usize) -> Self {
+        Self {
+            semaphore,
+            num_permits,
+        }
+    }
+
+    /// Forget the permits without releasing them to the semaphore.
+    pub fn forget(mut self) {
+        self.num_permits = 0;
+    }
+
+    pub fn num_permits(&self) -> usize {
+        self.num_permits
+    }
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    fn drop(&mut self) {
+        if self.num_permits > 0 {
+            self.semaphore.add_permits(self.num_permits);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! A channel that broadcasts the latest value to many receivers.
+
+use alloc::collections::BTreeMap;
+use core::ops::Deref;
+use core::task::Waker;
+
+use spin::rw_lock::{RwLock, RwLockReadGuard};
+
+use crate::prelude::*;
+
+/// Create a watch channel with an initial value.
+pub fn channel<T: Send + Sync>(value: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(value),
+        version: AtomicU64::new(0),
+        is_closed: AtomicBool::new(false),
+        num_receivers: AtomicU32::new(1),
+        next_receiver_id: AtomicU64::new(1),
+        wakers: Mutex::new(BTreeMap::new()),
+    });
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    let receiver = Receiver {
+        shared,
+        id: 0,
+        seen_version: 0,
+    };
+    (sender, receiver)
+}
+
+struct Shared<T> {
+    value: RwLock<T>,
+    version: AtomicU64,
+    is_closed: AtomicBool,
+    num_receivers: AtomicU32,
+    next_receiver_id: AtomicU64,
+    // The waker of each waiting receiver, keyed by its ID, so that a receiver
+    // polled many times takes up only one slot
+    wakers: Mutex<BTreeMap<u64, Waker>>,
+}
+
+impl<T> Shared<T> {
+    fn wake_all(&self) {
+        let wakers = core::mem::replace(&mut *self.wakers.lock(), BTreeMap::new());
+        wakers.into_iter().for_each(|(_, waker)| waker.wake());
+    }
+}
+
+/// The sending half of a watch channel.
+pub struct Sender<T: Send + Sync> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Send + Sync> Sender<T> {
+    /// Replace the value and notify the receivers.
+    ///
+    /// Returns an error if all receivers have been dropped.
+    pub fn send(&self, value: T) -> Result<()> {
+        if self.shared.num_receivers.load(Ordering::Acquire) == 0 {
+            return Err("all receivers have been dropped");
+        }
+
+        *self.shared.value.write() = value;
+        self.shared.version.fetch_add(1, Ordering::AcqRel);
+        self.shared.wake_all();
+        Ok(())
+    }
+
+    /// Returns a reference to the latest value.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref(self.shared.value.read())
+    }
+}
+
+impl<T: Send + Sync> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.is_closed.store(true, Ordering::Release);
+        self.shared.wake_all();
+    }
+}
+
+/// The receiving half of a watch channel, which can be cloned.
+pub struct Receiver<T: Send + Sync> {
+    shared: Arc<Shared<T>>,
+    id: u64,
+    seen_version: u64,
+}
+
+impl<T: Send + Sync> Receiver<T> {
+    /// Returns a reference to the latest value.
+    ///
+    /// The sender is blocked while the reference is held, so it should be
+    /// released as soon as possible.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref(self.shared.value.read())
+    }
+
+    /// Wait until a value that is newer than the last seen one is sent.
+    ///
+    /// Returns an error if the sender has been dropped.
+    pub async fn changed(&mut self) -> Result<()> {
+        let shared = self.shared.clone();
+        let id = self.id;
+        let seen_version = &mut self.seen_version;
+        futures::future::poll_fn(|cx| {
+            let mut check_version = || {
+                let version = shared.version.load(Ordering::Acquire);
+                if version != *seen_version {
+                    *seen_version = version;
+                    return Some(Ok(()));
+                }
+                if shared.is_closed.load(Ordering::Acquire) {
+                    return Some(Err("the sender has been dropped"));
+                }
+                None
+            };
+
+            if let Some(res) = check_version() {
+                return Poll::Ready(res);
+            }
+            {
+                let mut wakers = shared.wakers.lock();
+                match wakers.get(&id) {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    _ => {
+                        wakers.insert(id, cx.waker().clone());
+                    }
+                }
+            }
+            // Check again in case of a value sent before the waker is registered
+            match check_version() {
+                Some(res) => Poll::Ready(res),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+impl<T: Send + Sync> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.num_receivers.fetch_add(1, Ordering::AcqRel);
+        let id = self.shared.next_receiver_id.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+            id,
+            seen_version: self.seen_version,
+        }
+    }
+}
+
+impl<T: Send + Sync> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.num_receivers.fetch_sub(1, Ordering::AcqRel);
+        self.shared.wakers.lock().remove(&self.id);
+    }
+}
+
+/// A reference to the value of a watch channel.
+pub struct Ref<'a, T>(RwLockReadGuard<'a, T>);
+
+impl<'a, T> Deref for Ref<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &*self.0
+    }
+}
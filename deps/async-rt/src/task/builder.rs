@@ -0,0 +1,108 @@
+use alloc::string::String;
+use core::fmt::{self, Debug};
+
+use crate::prelude::*;
+use crate::runtime::Handle;
+use crate::sched::{Affinity, Priority, SchedInfo};
+use crate::task::{JoinHandle, LocalKey};
+
+/// A builder to spawn a task with non-default configurations.
+///
+/// ```ignore
+/// let join_handle = task::Builder::new()
+///     .name("conn-42")
+///     .priority(Priority::High)
+///     .spawn(future)?;
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    name: Option<String>,
+    affinity: Option<Affinity>,
+    priority: Priority,
+    // The inherited task-locals, each of which is a pair of a key and a value
+    locals: Vec<(u32, Box<dyn Send>)>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the task, which shows up in the debug output, in the
+    /// logs and in the panic reports about the task.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the initial affinity of the task. By default, the task may run on
+    /// any worker thread.
+    pub fn affinity(mut self, affinity: Affinity) -> Self {
+        self.affinity = Some(affinity);
+        self
+    }
+
+    /// Set the initial priority of the task.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Have the task inherit the task-local value with the given key from the
+    /// current task.
+    ///
+    /// The value is cloned when this method is called. Without a current task,
+    /// the new task starts with the initial value as usual.
+    pub fn inherit<T: Clone + Send + 'static>(mut self, key: &'static LocalKey<T>) -> Self {
+        if let Ok(value) = key.try_with(|value| value.clone()) {
+            self.locals.push((key.key(), Box::new(value)));
+        }
+        self
+    }
+
+    /// Spawn the task on the current runtime.
+    ///
+    /// An error is returned if the affinity is invalid (see `sched::set_affinity`).
+    ///
+    /// # Panics
+    ///
+    /// This method panics if not called from a worker thread of a runtime.
+    pub fn spawn<T: Send + 'static>(
+        self,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> Result<JoinHandle<T>> {
+        self.spawn_on(&Handle::current(), future)
+    }
+
+    /// Spawn the task on the given runtime.
+    ///
+    /// See `spawn`.
+    pub fn spawn_on<T: Send + 'static>(
+        self,
+        handle: &Handle,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> Result<JoinHandle<T>> {
+        let executor = handle.executor();
+        let sched_info = SchedInfo::new(executor.parallelism(), self.priority);
+        if let Some(affinity) = self.affinity {
+            affinity.validate(executor.parallelism())?;
+            *sched_info.affinity().write() = affinity;
+        }
+        let (task, join_handle) = crate::task::new_task(future, sched_info, self.name, executor);
+        for (key, value) in self.locals {
+            task.locals().replace(key, Some(value));
+        }
+        executor.accept_task(task);
+        Ok(join_handle)
+    }
+}
+
+impl Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("name", &self.name)
+            .field("affinity", &self.affinity)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
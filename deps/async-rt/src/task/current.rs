@@ -5,11 +5,20 @@ use crate::prelude::*;
 use crate::task::Task;
 
 pub fn current() -> Arc<Task> {
+    let current_task = try_current();
+    assert!(current_task.is_some());
+    current_task.unwrap()
+}
+
+/// Returns the current task, or `None` if not called from a task.
+pub(crate) fn try_current() -> Option<Arc<Task>> {
     let ptr = CURRENT.get();
-    assert!(ptr != ptr::null());
+    if ptr == ptr::null() {
+        return None;
+    }
     let current_task = unsafe { Arc::from_raw(ptr) };
     Arc::into_raw(current_task.clone());
-    current_task
+    Some(current_task)
 }
 
 pub(crate) fn set_current(task: Arc<Task>) {
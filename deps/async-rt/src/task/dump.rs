@@ -0,0 +1,43 @@
+use alloc::string::String;
+
+use crate::prelude::*;
+use crate::runtime::Handle;
+use crate::sched::{Affinity, Priority};
+use crate::task::{Task, TaskId, TaskState};
+
+/// A snapshot of the info of a live task.
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub tid: TaskId,
+    pub name: Option<String>,
+    pub state: TaskState,
+    /// The worker thread that the task was last scheduled to.
+    pub last_thread_id: u32,
+    pub affinity: Affinity,
+    pub priority: Priority,
+}
+
+impl TaskInfo {
+    pub(crate) fn new(task: &Task) -> Self {
+        let sched_info = task.sched_info();
+        Self {
+            tid: task.tid(),
+            name: task.name().map(String::from),
+            state: task.state(),
+            last_thread_id: sched_info.last_thread_id(),
+            affinity: sched_info.affinity().read().clone(),
+            priority: sched_info.priority(),
+        }
+    }
+}
+
+/// List the live tasks of the current runtime, in the order of their IDs.
+///
+/// Use `Handle::dump_tasks` to do so from outside the runtime.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime.
+pub fn dump() -> Vec<TaskInfo> {
+    Handle::current().dump_tasks()
+}
@@ -1,6 +1,6 @@
 use crate::prelude::*;
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TaskId(pub(crate) u64);
 
 impl TaskId {
@@ -1,34 +1,111 @@
 use alloc::sync::Weak;
+use core::fmt;
 use core::marker::PhantomData;
 use core::task::{Context, Poll, Waker};
 
+use spin::Once;
+
 use crate::prelude::*;
+use crate::task::{Task, TaskId};
 
 pub fn new<T: Send + 'static>() -> (JoinHandle<T>, OutputHandle<T>) {
-    let state = Arc::new(Mutex::new(State::new()));
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State::new()),
+        task: Once::new(),
+        is_aborted: AtomicBool::new(false),
+    });
     let output_handle = OutputHandle {
-        state: Arc::downgrade(&state),
+        inner: Some(Arc::downgrade(&inner)),
         phantom: PhantomData,
     };
     let join_handle = JoinHandle {
-        state: state,
+        inner: inner,
         phantom: PhantomData,
     };
     (join_handle, output_handle)
 }
 
+// The states shared by the join handle and the output handle of a task.
+struct Inner<T: Send + 'static> {
+    state: Mutex<State<T>>,
+    task: Once<(TaskId, Weak<Task>)>,
+    // Whether the join handle has aborted the task, which is how a blocking
+    // job, i.e., one without a task, learns of it
+    is_aborted: AtomicBool,
+}
+
+/// The error of a task that fails to produce its output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was aborted before it completed.
+    Cancelled,
+    /// The task panicked.
+    Panicked,
+    /// The runtime was shut down before the task completed.
+    Shutdown,
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => write!(f, "task was cancelled"),
+            JoinError::Panicked => write!(f, "task panicked"),
+            JoinError::Shutdown => write!(f, "runtime was shut down"),
+        }
+    }
+}
+
 pub struct JoinHandle<T: Send + 'static> {
-    state: Arc<Mutex<State<T>>>,
+    inner: Arc<Inner<T>>,
     phantom: PhantomData<T>,
 }
 
+impl<T: Send + 'static> JoinHandle<T> {
+    pub(crate) fn bind_task(&self, task: &Arc<Task>) {
+        self.inner
+            .task
+            .call_once(|| (task.tid(), Arc::downgrade(task)));
+    }
+
+    /// Bind a blocking job, which has an ID but no task.
+    pub(crate) fn bind_blocking(&self) {
+        self.inner.task.call_once(|| (TaskId::new(), Weak::new()));
+    }
+
+    /// Returns the ID of the task.
+    pub fn task_id(&self) -> TaskId {
+        self.inner.task.get().unwrap().0
+    }
+
+    /// Abort the task.
+    ///
+    /// The future of the task is dropped when the task is scheduled next time,
+    /// after which the task is joined with `JoinError::Cancelled`. Aborting a
+    /// task that has already finished has no effect.
+    ///
+    /// A blocking job that has not started yet is never run and is joined with
+    /// `JoinError::Cancelled` too. But one that has started runs to the end,
+    /// since there is no way to interrupt a blocking function.
+    pub fn abort(&self) {
+        self.inner.is_aborted.store(true, Ordering::Release);
+        if let Some(task) = self.inner.task.get().and_then(|task| task.1.upgrade()) {
+            task.abort(JoinError::Cancelled);
+        }
+    }
+
+    /// Returns whether the task has finished, either with or without an output.
+    pub fn is_finished(&self) -> bool {
+        self.inner.state.lock().is_finished()
+    }
+}
+
 impl<T: Send + 'static> Unpin for JoinHandle<T> {}
 
 impl<T: Send + 'static> Future for JoinHandle<T> {
-    type Output = T;
+    type Output = core::result::Result<T, JoinError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut state = self.state.lock();
+        let mut state = self.inner.state.lock();
         if let Some(output) = state.take_output(cx) {
             Poll::Ready(output)
         } else {
@@ -38,25 +115,62 @@ impl<T: Send + 'static> Future for JoinHandle<T> {
 }
 
 pub struct OutputHandle<T: Send + 'static> {
-    state: Weak<Mutex<State<T>>>,
+    inner: Option<Weak<Inner<T>>>,
     phantom: PhantomData<T>,
 }
 
 impl<T: Send + 'static> OutputHandle<T> {
-    pub fn set(self, output: T) {
-        if let Some(state) = self.state.upgrade() {
-            let mut state = state.lock();
-            state.set_output(output);
+    pub fn set(mut self, output: T) {
+        self.set_result(Ok(output));
+    }
+
+    /// Returns whether the join handle has aborted the task.
+    pub fn is_aborted(&self) -> bool {
+        self.inner
+            .as_ref()
+            .and_then(|inner| inner.upgrade())
+            .map_or(false, |inner| inner.is_aborted.load(Ordering::Acquire))
+    }
+
+    fn set_result(&mut self, result: core::result::Result<T, JoinError>) {
+        if let Some(inner) = self.inner.take().and_then(|inner| inner.upgrade()) {
+            let mut state = inner.state.lock();
+            state.set_output(result);
         }
     }
 }
 
+impl<T: Send + 'static> Drop for OutputHandle<T> {
+    fn drop(&mut self) {
+        let inner = match self.inner.as_ref().and_then(|inner| inner.upgrade()) {
+            None => return,
+            Some(inner) => inner,
+        };
+
+        // The future of the task is dropped before it completes, either because
+        // the task has panicked or because it has been aborted. A panicking task
+        // may drop its future while unwinding, i.e., before it is marked panicked.
+        let task = inner.task.get().and_then(|task| task.1.upgrade());
+        let error = if crate::executor::panicking() {
+            JoinError::Panicked
+        } else {
+            // A task that is dropped without being aborted, e.g., because it can
+            // never be woken up again, is considered as cancelled
+            let dropping_error =
+                || crate::task::dropping_task_error().unwrap_or(JoinError::Cancelled);
+            task.map_or_else(dropping_error, |task| task.join_error())
+        };
+        drop(inner);
+        self.set_result(Err(error));
+    }
+}
+
 // The state of a task that is to be joined.
 #[derive(Debug)]
 enum State<T: Send + 'static> {
     Init,
     Pending(Waker),
-    Ready(T),
+    Ready(core::result::Result<T, JoinError>),
     Finish,
 }
 
@@ -65,7 +179,7 @@ impl<T: Send + 'static> State<T> {
         State::Init
     }
 
-    pub fn set_output(&mut self, value: T) {
+    pub fn set_output(&mut self, value: core::result::Result<T, JoinError>) {
         *self = match self {
             State::Init => State::Ready(value),
             State::Pending(waker) => {
@@ -78,7 +192,10 @@ impl<T: Send + 'static> State<T> {
         };
     }
 
-    pub fn take_output(&mut self, cx: &mut Context<'_>) -> Option<T> {
+    pub fn take_output(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Option<core::result::Result<T, JoinError>> {
         match self {
             State::Init | State::Pending(_) => {
                 *self = State::Pending(cx.waker().clone());
@@ -99,4 +216,11 @@ impl<T: Send + 'static> State<T> {
             }
         }
     }
+
+    pub fn is_finished(&self) -> bool {
+        match self {
+            State::Init | State::Pending(_) => false,
+            State::Ready(_) | State::Finish => true,
+        }
+    }
 }
@@ -0,0 +1,108 @@
+use futures::future::poll_fn;
+use futures::stream::{FuturesUnordered, Stream};
+
+use crate::prelude::*;
+use crate::runtime::Handle;
+use crate::task::{JoinError, JoinHandle, TaskId};
+
+/// A group of tasks that are joined in the order of completion.
+///
+/// The tasks that are still in the set when it is dropped are aborted, so a
+/// server can tie the tasks of its connections to the lifetime of its accept
+/// loop. Blocking jobs can be inserted too, but only the ones that have not
+/// started are aborted (see `JoinHandle::abort`).
+///
+/// ```ignore
+/// let mut conns = JoinSet::new();
+/// while let Some(conn) = listener.accept().await {
+///     conns.spawn(handle_conn(conn));
+/// }
+/// conns.shutdown().await;
+/// ```
+pub struct JoinSet<T: Send + 'static> {
+    join_handles: FuturesUnordered<JoinHandle<T>>,
+}
+
+impl<T: Send + 'static> JoinSet<T> {
+    pub fn new() -> Self {
+        Self {
+            join_handles: FuturesUnordered::new(),
+        }
+    }
+
+    /// Returns the number of tasks in the set, including the finished ones
+    /// that have not been joined yet.
+    pub fn len(&self) -> usize {
+        self.join_handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.join_handles.is_empty()
+    }
+
+    /// Spawn a new task on the current runtime into the set.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if not called from a worker thread of a runtime.
+    pub fn spawn(&mut self, future: impl Future<Output = T> + 'static + Send) -> TaskId {
+        self.insert(crate::task::spawn(future))
+    }
+
+    /// Spawn a new task on the given runtime into the set.
+    pub fn spawn_on(
+        &mut self,
+        handle: &Handle,
+        future: impl Future<Output = T> + 'static + Send,
+    ) -> TaskId {
+        self.insert(handle.spawn(future))
+    }
+
+    /// Add a spawned task to the set, e.g., one spawned by `task::Builder`.
+    pub fn insert(&mut self, join_handle: JoinHandle<T>) -> TaskId {
+        let tid = join_handle.task_id();
+        self.join_handles.push(join_handle);
+        tid
+    }
+
+    /// Wait for one of the tasks in the set to finish and return its result.
+    ///
+    /// Returns `None` if the set is empty.
+    pub async fn join_next(&mut self) -> Option<core::result::Result<T, JoinError>> {
+        poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    /// Poll for the result of the next task to finish. See `join_next`.
+    pub fn poll_join_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<core::result::Result<T, JoinError>>> {
+        Pin::new(&mut self.join_handles).poll_next(cx)
+    }
+
+    /// Abort all tasks in the set. The tasks stay in the set until joined.
+    pub fn abort_all(&self) {
+        for join_handle in self.join_handles.iter() {
+            join_handle.abort();
+        }
+    }
+
+    /// Abort all tasks in the set and wait for them to finish, after which the
+    /// set is empty.
+    pub async fn shutdown(&mut self) {
+        self.abort_all();
+        while self.join_next().await.is_some() {}
+    }
+}
+
+impl<T: Send + 'static> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + 'static> Drop for JoinSet<T> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
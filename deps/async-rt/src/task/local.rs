@@ -0,0 +1,93 @@
+use core::mem::ManuallyDrop;
+
+use crate::prelude::*;
+use crate::sched::SchedInfo;
+use crate::task::{join, JoinHandle, Task};
+
+/// Spawn a new task that runs only on the current worker thread.
+///
+/// Unlike `spawn`, the future does not have to be `Send`. The task is kept in
+/// a queue of the current thread that other threads never dequeue from, and
+/// its affinity cannot be changed. The priority of a local task is ignored.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime.
+pub fn spawn_local<T: Send + 'static>(future: impl Future<Output = T> + 'static) -> JoinHandle<T> {
+    let (executor, thread_id) = match crate::executor::current() {
+        None => panic!("not called from a worker thread of a runtime"),
+        Some(executor) => (executor, crate::executor::current_thread_id().unwrap()),
+    };
+
+    let (join_handle, output_handle) = join::new();
+    let future = LocalFuture::new(async move {
+        let output = future.await;
+        output_handle.set(output);
+    });
+    let sched_info = SchedInfo::new_local(executor.parallelism(), thread_id as u32);
+    let task = Arc::new(Task::new(future, sched_info, None, &executor));
+    join_handle.bind_task(&task);
+    executor.accept_task(task);
+    join_handle
+}
+
+/// A future that may only be polled on the thread that creates it.
+///
+/// This allows storing a `!Send` future in a task, which requires `Send`. The
+/// executor guarantees that a local task is only polled on its own thread, and
+/// that it is torn down there before the thread exits. Both are checked: a poll
+/// on another thread panics, and a drop on another thread, which would break
+/// the guarantee, leaks the future instead of dropping it.
+struct LocalFuture<F> {
+    future: ManuallyDrop<F>,
+    thread_token: usize,
+}
+
+// Safety. The inner future is never touched from other threads, which is
+// checked by both `poll` and `drop`.
+unsafe impl<F> Send for LocalFuture<F> {}
+
+impl<F> LocalFuture<F> {
+    pub fn new(future: F) -> Self {
+        Self {
+            future: ManuallyDrop::new(future),
+            thread_token: thread_token(),
+        }
+    }
+
+    fn is_owned_by_current_thread(&self) -> bool {
+        self.thread_token == thread_token()
+    }
+}
+
+impl<F: Future> Future for LocalFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(
+            self.is_owned_by_current_thread(),
+            "a local task must not be polled by another thread"
+        );
+        // Safety. The future is never moved out of the pinned self.
+        let future = unsafe { self.map_unchecked_mut(|self_| &mut *self_.future) };
+        future.poll(cx)
+    }
+}
+
+impl<F> Drop for LocalFuture<F> {
+    fn drop(&mut self) {
+        if !self.is_owned_by_current_thread() {
+            log::warn!("a local task is leaked since it is dropped by another thread");
+            return;
+        }
+        unsafe { ManuallyDrop::drop(&mut self.future) };
+    }
+}
+
+/// Returns a token that is unique among the threads that are alive.
+fn thread_token() -> usize {
+    #[thread_local]
+    static TOKEN: u8 = 0;
+
+    &TOKEN as *const u8 as usize
+}
@@ -18,22 +18,63 @@ impl<T: Send + 'static> LocalKey<T> {
 }
 
 impl<T: Send + 'static> LocalKey<T> {
-    /// Attempts to get a reference to the task-local value with this key.
+    /// Gets a reference to the task-local value with this key.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if not called from a task or if the task-locals of the
+    /// task are being dropped. See `try_with` for a non-panicking version.
     pub fn with<F, R>(&'static self, f: F) -> R
     where
         F: FnOnce(&T) -> R,
     {
-        let current = crate::task::current();
+        match self.try_with(f) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Attempts to get a reference to the task-local value with this key.
+    ///
+    /// An error is returned if not called from a task or if the task-locals of
+    /// the task are being dropped.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let current = crate::task::try_current().ok_or("not called from a task")?;
 
         // Prepare the numeric key, initialization function, and the map of task-locals.
         let key = self.key();
         let init = || Box::new((self.init)()) as Box<dyn Send>;
 
         // Get the value in the map of task-locals, or initialize and insert one.
-        let value: *const dyn Send = current.locals().get_or_insert(key, init);
+        let value: *const dyn Send = current
+            .locals()
+            .try_get_or_insert(key, init)
+            .ok_or("can't access task-locals while the task is being dropped")?;
 
         // Call the closure with the value passed as an argument.
-        unsafe { f(&*(value as *const T)) }
+        Ok(unsafe { f(&*(value as *const T)) })
+    }
+
+    /// Run a future with the task-local value with this key set to the given
+    /// value.
+    ///
+    /// The value is in place only while the future is being polled; otherwise,
+    /// the task sees its own value. The value is dropped along with the returned
+    /// future. Outside of any task (e.g., when polled by a plain executor), the
+    /// future is polled without the value, since there is no place for it.
+    ///
+    /// ```ignore
+    /// REQUEST_ID.scope(42, handle_request(req)).await;
+    /// ```
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<F> {
+        TaskLocalFuture {
+            key: self.key(),
+            value: Some(Box::new(value)),
+            future,
+        }
     }
 
     /// Returns the numeric key associated with this task-local.
@@ -78,10 +119,16 @@ impl LocalsMap {
     }
 
     /// Returns a task-local value associated with `key` or inserts one constructed by `init`.
+    ///
+    /// Returns `None` if the task-locals are being dropped.
     #[inline]
-    pub fn get_or_insert(&self, key: u32, init: impl FnOnce() -> Box<dyn Send>) -> &dyn Send {
+    pub fn try_get_or_insert(
+        &self,
+        key: u32,
+        init: impl FnOnce() -> Box<dyn Send>,
+    ) -> Option<&dyn Send> {
         match unsafe { (*self.entries.get()).as_mut() } {
-            None => panic!("can't access task-locals while the task is being dropped"),
+            None => None,
             Some(entries) => {
                 let index = match entries.binary_search_by_key(&key, |e| e.key) {
                     Ok(i) => i,
@@ -91,11 +138,29 @@ impl LocalsMap {
                         i
                     }
                 };
-                &*entries[index].value
+                Some(&*entries[index].value)
             }
         }
     }
 
+    /// Replaces the task-local value associated with `key`, or removes it if
+    /// `value` is `None`. Returns the old value, if any.
+    pub fn replace(&self, key: u32, value: Option<Box<dyn Send>>) -> Option<Box<dyn Send>> {
+        let entries = match unsafe { (*self.entries.get()).as_mut() } {
+            None => panic!("can't access task-locals while the task is being dropped"),
+            Some(entries) => entries,
+        };
+        match (entries.binary_search_by_key(&key, |e| e.key), value) {
+            (Ok(i), Some(value)) => Some(core::mem::replace(&mut entries[i].value, value)),
+            (Ok(i), None) => Some(entries.remove(i).value),
+            (Err(i), Some(value)) => {
+                entries.insert(i, Entry { key, value });
+                None
+            }
+            (Err(_), None) => None,
+        }
+    }
+
     /// Clears the map and drops all task-locals.
     ///
     /// This method is only safe to call at the end of the task.
@@ -107,6 +172,51 @@ impl LocalsMap {
     }
 }
 
+/// A future that runs another one with a task-local value in place. See
+/// `LocalKey::scope`.
+pub struct TaskLocalFuture<F> {
+    key: u32,
+    value: Option<Box<dyn Send>>,
+    future: F,
+}
+
+impl<F: Future> Future for TaskLocalFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Puts the value of the task back when dropped, even if the poll panics
+        struct Restore<'a> {
+            locals: &'a LocalsMap,
+            key: u32,
+            prev: Option<Box<dyn Send>>,
+            value: &'a mut Option<Box<dyn Send>>,
+        }
+
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                *self.value = self.locals.replace(self.key, self.prev.take());
+            }
+        }
+
+        // Safety. The future is never moved out of the pinned self.
+        let self_ = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut self_.future) };
+        let current = match crate::task::try_current() {
+            Some(current) => current,
+            None => return future.poll(cx),
+        };
+        let locals = current.locals();
+        let prev = locals.replace(self_.key, self_.value.take());
+        let _restore = Restore {
+            locals,
+            key: self_.key,
+            prev,
+            value: &mut self_.value,
+        };
+        future.poll(cx)
+    }
+}
+
 /// A key-value entry in a map of task-locals.
 struct Entry {
     /// Key identifying the task-local variable.
@@ -1,56 +1,124 @@
+use alloc::string::String;
 use alloc::sync::Arc;
 use core::future::Future;
 
-use crate::executor::EXECUTOR;
+use crate::executor::Executor;
 use crate::prelude::*;
+use crate::runtime::Handle;
+use crate::sched::{Priority, SchedInfo};
 
+pub use self::builder::Builder;
 pub use self::current::current;
+pub use self::dump::{dump, TaskInfo};
 pub use self::id::TaskId;
-pub use self::join::JoinHandle;
-pub use self::locals::LocalKey;
-pub use self::task::Task;
+pub use self::join::{JoinError, JoinHandle};
+pub use self::join_set::JoinSet;
+pub use self::local::spawn_local;
+pub use self::locals::{LocalKey, TaskLocalFuture};
+pub use self::task::{Task, TaskState};
 
-pub(crate) use self::current::{reset_current, set_current};
+pub(crate) use self::current::{reset_current, set_current, try_current};
 pub(crate) use self::locals::LocalsMap;
+pub(crate) use self::task::dropping_task_error;
 
+mod builder;
 mod current;
+mod dump;
 mod id;
 mod join;
+mod join_set;
+mod local;
 mod locals;
 mod task;
 
+/// Spawn a new task on the current runtime.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime. Use
+/// `Handle::spawn` to spawn a task from elsewhere.
 pub fn spawn<T: Send + 'static>(future: impl Future<Output = T> + 'static + Send) -> JoinHandle<T> {
-    let (join_handle, output_handle) = join::new();
-    let future = async move {
-        let output = future.await;
-        output_handle.set(output);
-    };
-    let task = Arc::new(Task::new(future));
-    EXECUTOR.accept_task(task);
-    join_handle
+    Handle::current().spawn(future)
 }
 
-pub fn block_on<T: Send + 'static>(future: impl Future<Output = T> + 'static + Send) -> T {
-    let output_slot: Arc<Mutex<Option<T>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new(AtomicBool::new(false));
+/// Spawn a new task with the given priority on the current runtime.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime.
+pub fn spawn_with_priority<T: Send + 'static>(
+    priority: Priority,
+    future: impl Future<Output = T> + 'static + Send,
+) -> JoinHandle<T> {
+    Handle::current().spawn_with_priority(priority, future)
+}
 
-    let future = {
-        let output_slot = output_slot.clone();
-        let completed = completed.clone();
+/// Run a blocking function on a thread dedicated to blocking jobs of the
+/// current runtime.
+///
+/// Blocking calls (e.g., ocalls) should be made this way so that they do not
+/// stall the worker threads. Awaiting the returned handle gives the output of
+/// the function. A job that is refused by a shut-down runtime, or aborted
+/// before it starts (see `JoinHandle::abort`), is joined with
+/// `JoinError::Cancelled`.
+///
+/// # Panics
+///
+/// This function panics if not called from a worker thread of a runtime. Use
+/// `Handle::spawn_blocking` to spawn a blocking job from elsewhere.
+pub fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> JoinHandle<T> {
+    Handle::current().spawn_blocking(f)
+}
+
+/// Run a future and block the current thread until it completes.
+///
+/// The future runs on the runtime of the current worker thread, or on the
+/// global executor (see `executor::run_tasks`) if called from elsewhere.
+#[deprecated(note = "use `Runtime::block_on` or `Handle::block_on` instead")]
+pub fn block_on<T: Send + 'static>(future: impl Future<Output = T> + 'static + Send) -> T {
+    let handle = Handle::try_current().unwrap_or_else(crate::executor::global_handle);
+    handle.block_on(future)
+}
 
-        async move {
-            let output = future.await;
+/// Spawn a new task on the current runtime unless the executor is overloaded.
+///
+/// Unlike `spawn`, which always succeeds, this function returns an error if the
+/// run queue of the target thread is full, so that the caller (e.g., an accept
+/// loop) can shed load instead.
+pub fn try_spawn<T: Send + 'static>(
+    future: impl Future<Output = T> + 'static + Send,
+) -> Result<JoinHandle<T>> {
+    Handle::current().try_spawn(future)
+}
 
-            let mut output_slot = output_slot.lock();
-            *output_slot = Some(output);
-            completed.store(true, Ordering::Release);
+/// Create a blocking job for a function, along with the handle to join the job.
+pub(crate) fn new_blocking_job<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> (impl FnOnce() + Send + 'static, JoinHandle<T>) {
+    let (join_handle, output_handle) = join::new();
+    join_handle.bind_blocking();
+    let job = move || {
+        // Dropping the output handle joins an aborted job as cancelled
+        if !output_handle.is_aborted() {
+            output_handle.set(f());
         }
     };
+    (job, join_handle)
+}
 
-    let task = Arc::new(Task::new(future));
-    EXECUTOR.accept_task(task);
-    while !completed.load(Ordering::Acquire) {}
-
-    let mut output_slot = output_slot.lock();
-    output_slot.take().unwrap()
+/// Create a task for a future, along with the handle to join the task.
+pub(crate) fn new_task<T: Send + 'static>(
+    future: impl Future<Output = T> + 'static + Send,
+    sched_info: SchedInfo,
+    name: Option<String>,
+    executor: &Arc<Executor>,
+) -> (Arc<Task>, JoinHandle<T>) {
+    let (join_handle, output_handle) = join::new();
+    let future = async move {
+        let output = future.await;
+        output_handle.set(output);
+    };
+    let task = Arc::new(Task::new(future, sched_info, name, executor));
+    join_handle.bind_task(&task);
+    (task, join_handle)
 }
@@ -1,31 +1,89 @@
+use alloc::string::String;
+use alloc::sync::Weak;
+use core::cell::{Cell, UnsafeCell};
 use core::fmt::{self, Debug};
+use core::sync::atomic::AtomicU8;
 
 use futures::future::{BoxFuture, FutureExt};
 use futures::task::ArcWake;
+use spin::Once;
 
-use crate::executor::EXECUTOR;
+use crate::executor::Executor;
 use crate::prelude::*;
 use crate::sched::SchedInfo;
-use crate::task::{LocalsMap, TaskId};
+use crate::task::{JoinError, LocalsMap, TaskId};
 
 pub struct Task {
     tid: TaskId,
+    name: Option<String>,
     sched_info: SchedInfo,
-    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    // Only accessed by the thread that runs the task, as guarded by the state
+    future: UnsafeCell<Option<BoxFuture<'static, ()>>>,
     locals: LocalsMap,
+    abort_error: Once<JoinError>,
+    is_panicked: AtomicBool,
+    state: AtomicU8,
+    executor: Weak<Executor>,
+}
+
+/// The scheduling state of a task.
+///
+/// A wakeup enqueues a task only if it is idle. A task woken up while running
+/// is marked as notified instead and enqueued once its poll returns. So a task
+/// is in a run queue at most once, no matter how many times it is woken up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TaskState {
+    /// The task is waiting to be woken up.
+    Idle = 0,
+    /// The task has been woken up and is waiting in a run queue.
+    Queued = 1,
+    /// The task is being polled by a worker thread.
+    Running = 2,
+    /// The task has been woken up while being polled, so it will be enqueued
+    /// again once the poll returns.
+    Notified = 3,
+    /// The task will never be polled again.
+    Completed = 4,
+}
+
+impl TaskState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => TaskState::Idle,
+            1 => TaskState::Queued,
+            2 => TaskState::Running,
+            3 => TaskState::Notified,
+            4 => TaskState::Completed,
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl Task {
-    pub fn new(future: impl Future<Output = ()> + 'static + Send) -> Self {
+    pub(crate) fn new(
+        future: impl Future<Output = ()> + 'static + Send,
+        sched_info: SchedInfo,
+        name: Option<String>,
+        executor: &Arc<Executor>,
+    ) -> Self {
         let tid = TaskId::new();
-        let sched_info = SchedInfo::new();
-        let future = Mutex::new(Some(future.boxed()));
+        let future = UnsafeCell::new(Some(future.boxed()));
         let locals = LocalsMap::new();
+        let abort_error = Once::new();
+        let is_panicked = AtomicBool::new(false);
+        let state = AtomicU8::new(TaskState::Idle as u8);
+        let executor = Arc::downgrade(executor);
         Self {
             tid,
+            name,
             sched_info,
             future,
             locals,
+            abort_error,
+            is_panicked,
+            state,
+            executor,
         }
     }
 
@@ -33,17 +91,138 @@ impl Task {
         self.tid
     }
 
+    /// Returns the name of the task, if it is given one with `task::Builder`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn sched_info(&self) -> &SchedInfo {
         &self.sched_info
     }
 
-    pub(crate) fn future(&self) -> &Mutex<Option<BoxFuture<'static, ()>>> {
-        &self.future
+    /// Take the future out of the task.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only one that runs the task, i.e., the one that
+    /// has dequeued the task or that holds it before it is accepted.
+    pub(crate) unsafe fn take_future(&self) -> Option<BoxFuture<'static, ()>> {
+        (*self.future.get()).take()
+    }
+
+    /// Put the future back into the task after a poll.
+    ///
+    /// # Safety
+    ///
+    /// The same as `take_future`.
+    pub(crate) unsafe fn put_future(&self, future: BoxFuture<'static, ()>) {
+        *self.future.get() = Some(future);
     }
 
     pub(crate) fn locals(&self) -> &LocalsMap {
         &self.locals
     }
+
+    /// Abort the task, whose future will be dropped at its next scheduling.
+    ///
+    /// Unless the task completes or panics first, it is joined with the given error.
+    pub(crate) fn abort(self: &Arc<Self>, error: JoinError) {
+        if self.set_aborted(error) {
+            ArcWake::wake_by_ref(self);
+        }
+    }
+
+    /// Mark the task as aborted without waking it up. Returns false if the task
+    /// has already been aborted.
+    pub(crate) fn set_aborted(&self, error: JoinError) -> bool {
+        let mut is_first = false;
+        self.abort_error.call_once(|| {
+            is_first = true;
+            error
+        });
+        is_first
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.abort_error.is_completed()
+    }
+
+    pub(crate) fn set_panicked(&self) {
+        self.is_panicked.store(true, Ordering::Release);
+    }
+
+    pub fn is_panicked(&self) -> bool {
+        self.is_panicked.load(Ordering::Acquire)
+    }
+
+    pub fn state(&self) -> TaskState {
+        TaskState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    /// Move the task to the queued state upon a wakeup. Returns whether the
+    /// task needs to be enqueued, which is only the case if it was idle.
+    pub(crate) fn transition_to_queued(&self) -> bool {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let new_state = match TaskState::from_u8(state) {
+                TaskState::Idle => TaskState::Queued,
+                TaskState::Running => TaskState::Notified,
+                TaskState::Queued | TaskState::Notified | TaskState::Completed => return false,
+            };
+            match self.state.compare_exchange_weak(
+                state,
+                new_state as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return new_state == TaskState::Queued,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Move a dequeued task to the running state.
+    pub(crate) fn transition_to_running(&self) {
+        self.state
+            .store(TaskState::Running as u8, Ordering::Release);
+    }
+
+    /// Move a task whose poll has returned pending to the idle state. Returns
+    /// whether the task has been woken up during the poll, in which case it is
+    /// queued instead and needs to be enqueued.
+    pub(crate) fn transition_to_idle(&self) -> bool {
+        match self.state.compare_exchange(
+            TaskState::Running as u8,
+            TaskState::Idle as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => false,
+            Err(_) => {
+                // Only a wakeup may change the state of a running task
+                self.state.store(TaskState::Queued as u8, Ordering::Release);
+                true
+            }
+        }
+    }
+
+    /// Mark the task as completed, after which any wakeup is ignored.
+    pub(crate) fn transition_to_completed(&self) {
+        self.state
+            .store(TaskState::Completed as u8, Ordering::Release);
+    }
+
+    /// Returns the error that the task is joined with if its future is dropped
+    /// before it completes.
+    pub(crate) fn join_error(&self) -> JoinError {
+        if self.is_panicked() {
+            return JoinError::Panicked;
+        }
+        self.abort_error
+            .get()
+            .cloned()
+            .unwrap_or(JoinError::Cancelled)
+    }
 }
 
 unsafe impl Sync for Task {}
@@ -53,21 +232,55 @@ impl Drop for Task {
         // Drop the locals explicitly so that we can take care of any potential panics
         // here. One possible reason of panic is the drop method of a task-local variable
         // requires accessinng another already-dropped task-local variable.
-        // TODO: handle panic
+        //
+        // Normally, the locals have already been cleared by the executor, which
+        // confines such panics to the task. So this only matters for the tasks
+        // that are dropped without being run to the end.
         unsafe {
             self.locals.clear();
         }
+
+        // A task that is dropped with its future, e.g., when it is left in a
+        // run queue by shutdown, can no longer be found by its output handle
+        if let Some(future) = self.future.get_mut().take() {
+            let last_error = DROPPING_TASK_ERROR.replace(Some(self.join_error()));
+            drop(future);
+            DROPPING_TASK_ERROR.set(last_error);
+        }
+
+        if let Some(executor) = self.executor.upgrade() {
+            executor.unregister_task(self.tid);
+        }
     }
 }
 
+// The join error of the task whose future is being dropped by `Task::drop` on
+// the current thread
+#[thread_local]
+static DROPPING_TASK_ERROR: Cell<Option<JoinError>> = Cell::new(None);
+
+/// Returns the join error of the task that is being dropped, if any. See
+/// `Task::drop`.
+pub(crate) fn dropping_task_error() -> Option<JoinError> {
+    DROPPING_TASK_ERROR.get()
+}
+
 impl ArcWake for Task {
     fn wake_by_ref(arc_self: &Arc<Self>) {
-        EXECUTOR.accept_task(arc_self.clone());
+        // The task outlives its executor if the runtime has been dropped
+        if let Some(executor) = arc_self.executor.upgrade() {
+            executor.wake_task(arc_self.clone());
+        }
     }
 }
 
 impl Debug for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Task").field("tid", &self.tid).finish()
+        let mut f = f.debug_struct("Task");
+        f.field("tid", &self.tid);
+        if let Some(name) = &self.name {
+            f.field("name", name);
+        }
+        f.finish()
     }
 }
@@ -0,0 +1,71 @@
+use core::time::Duration;
+
+use spin::Once;
+
+use crate::prelude::*;
+
+/// A monotonic clock that drives the timers.
+///
+/// The clock is pluggable since there is no universal time source. On Linux,
+/// `StdClock` is used by default when std is available. Inside SGX, the
+/// enclave should install a clock backed by the untrusted time or by the
+/// `Timeout` operations of io_uring.
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since an arbitrary but fixed point in the past.
+    fn now(&self) -> Duration;
+}
+
+/// A clock backed by `std::time::Instant`.
+#[cfg(any(test, feature = "std"))]
+pub struct StdClock {
+    base: std::time::Instant,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl StdClock {
+    pub fn new() -> Self {
+        let base = std::time::Instant::now();
+        Self { base }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        self.base.elapsed()
+    }
+}
+
+static CLOCK: Once<Box<dyn Clock>> = Once::new();
+
+/// Set the clock of the timers, which can only be done once.
+///
+/// This must be done before any timer is used.
+pub fn set_clock(clock: impl Clock + 'static) -> Result<()> {
+    let mut is_set = false;
+    CLOCK.call_once(|| {
+        is_set = true;
+        Box::new(clock)
+    });
+    if !is_set {
+        return Err("the clock has already been set");
+    }
+    Ok(())
+}
+
+/// Returns the clock, which is `None` if no clock is available.
+pub(crate) fn clock() -> Option<&'static dyn Clock> {
+    #[cfg(any(test, feature = "std"))]
+    CLOCK.call_once(|| Box::new(StdClock::new()));
+
+    CLOCK.get().map(|clock| &**clock)
+}
+
+/// Returns the current time of the clock.
+///
+/// # Panics
+///
+/// This function panics if no clock has been set.
+pub fn now() -> Duration {
+    clock().expect("no clock has been set").now()
+}
@@ -0,0 +1,40 @@
+use core::time::Duration;
+
+/// Create an interval that ticks once every period, starting from now.
+///
+/// # Panics
+///
+/// This function panics if the period is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::from_secs(0), "the period must be non-zero");
+    let next_deadline = crate::time::now();
+    Interval {
+        period,
+        next_deadline,
+    }
+}
+
+/// A stream of ticks that are evenly spaced in time.
+///
+/// If a tick is missed because the task is late, the next ticks fire
+/// immediately until the interval catches up.
+pub struct Interval {
+    period: Duration,
+    next_deadline: Duration,
+}
+
+impl Interval {
+    /// Wait until the next tick, which returns the deadline of the tick.
+    ///
+    /// The first tick completes immediately.
+    pub async fn tick(&mut self) -> Duration {
+        let deadline = self.next_deadline;
+        self.next_deadline += self.period;
+        crate::time::sleep_until(deadline).await;
+        deadline
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
@@ -0,0 +1,119 @@
+//! Timers, including sleep, timeout and interval.
+//!
+//! Each executor keeps its timers in a timer wheel of its own, which is
+//! advanced by its worker threads as they run. A timer belongs to the executor
+//! whose worker thread polls it first; a timer that is first polled elsewhere
+//! (e.g., in `Runtime::block_on`) goes to a global wheel, which the worker
+//! threads of all executors advance. So the timers only work with a running
+//! executor.
+
+use core::task::Waker;
+use core::time::Duration;
+
+use crate::prelude::*;
+
+pub use self::clock::{now, set_clock, Clock};
+#[cfg(any(test, feature = "std"))]
+pub use self::clock::StdClock;
+pub use self::interval::{interval, Interval};
+pub use self::sleep::{sleep, sleep_until, Sleep};
+pub use self::timeout::{timeout, Timeout};
+
+pub(crate) use self::clock::clock;
+use self::wheel::{TimerEntry, TimerWheel};
+
+mod clock;
+mod interval;
+mod sleep;
+mod timeout;
+mod wheel;
+
+/// The timers of an executor.
+pub(crate) struct Timers {
+    wheel: Mutex<TimerWheel>,
+    // The number of pending timers, which allows checking the wheel without
+    // locking. The cancelled timers are not counted even if still in the wheel.
+    num_timers: Arc<AtomicU64>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        let now = clock().map_or(Duration::from_secs(0), |clock| clock.now());
+        Self {
+            wheel: Mutex::new(TimerWheel::new(now)),
+            num_timers: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Add a timer. Returns false if the timer has already expired.
+    fn add(&self, entry: Arc<TimerEntry>) -> bool {
+        let mut wheel = self.wheel.lock();
+        if !wheel.insert(entry.clone()) {
+            return false;
+        }
+        entry.count_in(&self.num_timers);
+        true
+    }
+
+    /// Returns the number of pending timers.
+    pub fn num_timers(&self) -> u64 {
+        self.num_timers.load(Ordering::Acquire)
+    }
+
+    /// Returns whether there are any pending timers.
+    pub fn has_timers(&self) -> bool {
+        self.num_timers() > 0
+    }
+
+    /// Fire the expired timers, if any. Returns whether any timers are fired.
+    ///
+    /// This is called by the worker threads repeatedly. It is cheap when there
+    /// are no timers and never blocks on a thread that is doing the same thing.
+    pub fn fire(&self) -> bool {
+        if !self.has_timers() {
+            return false;
+        }
+        let now = match clock() {
+            None => return false,
+            Some(clock) => clock.now(),
+        };
+
+        let mut wakers: Vec<Waker> = Vec::new();
+        {
+            let mut wheel = match self.wheel.try_lock() {
+                None => return false,
+                Some(wheel) => wheel,
+            };
+            wheel.advance(now, &mut wakers);
+        }
+
+        // Wake up the tasks without holding the lock of the wheel
+        let has_fired = !wakers.is_empty();
+        wakers.into_iter().for_each(|waker| waker.wake());
+        has_fired
+    }
+}
+
+lazy_static! {
+    // The timers that are first polled outside of any worker thread
+    static ref GLOBAL_TIMERS: Timers = Timers::new();
+}
+
+/// Add a timer to the executor of the current worker thread, or to the global
+/// timers if there is none. Returns false if the timer has already expired.
+fn add_timer(entry: Arc<TimerEntry>) -> bool {
+    match crate::executor::current() {
+        Some(executor) => executor.timers().add(entry),
+        None => GLOBAL_TIMERS.add(entry),
+    }
+}
+
+/// Returns whether there are any pending global timers.
+pub(crate) fn has_global_timers() -> bool {
+    GLOBAL_TIMERS.has_timers()
+}
+
+/// Fire the expired global timers, if any. Returns whether any timers are fired.
+pub(crate) fn fire_global_timers() -> bool {
+    GLOBAL_TIMERS.fire()
+}
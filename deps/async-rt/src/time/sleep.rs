@@ -0,0 +1,71 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::time::TimerEntry;
+
+/// Sleep for the given duration.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(crate::time::now() + duration)
+}
+
+/// Sleep until the given deadline, which is a point of time of the clock.
+pub fn sleep_until(deadline: Duration) -> Sleep {
+    Sleep::new(deadline)
+}
+
+/// A future that completes at a deadline.
+pub struct Sleep {
+    entry: Arc<TimerEntry>,
+    is_added: bool,
+}
+
+impl Sleep {
+    pub fn new(deadline: Duration) -> Self {
+        let entry = Arc::new(TimerEntry::new(deadline));
+        Self {
+            entry,
+            is_added: false,
+        }
+    }
+
+    pub fn deadline(&self) -> Duration {
+        self.entry.deadline()
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.entry.is_fired() || crate::time::now() >= self.entry.deadline()
+    }
+}
+
+impl Unpin for Sleep {}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let self_ = self.as_mut().get_mut();
+        if !self_.is_added {
+            self_.is_added = true;
+            if !crate::time::add_timer(self_.entry.clone()) {
+                return Poll::Ready(());
+            }
+        }
+
+        // Check the timer after setting the waker so that a concurrent firing
+        // of the timer cannot be missed
+        self_.entry.set_waker(cx.waker());
+        if self_.entry.is_fired() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if self.is_added && !self.entry.is_fired() {
+            self.entry.cancel();
+        }
+    }
+}
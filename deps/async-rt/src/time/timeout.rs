@@ -0,0 +1,42 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::time::Sleep;
+
+/// Await a future until it completes or the given duration has elapsed.
+///
+/// The future is dropped if it does not complete in time, in which case an
+/// error is returned.
+///
+/// # Panics
+///
+/// This function panics if no clock has been set (see `set_clock`), which is
+/// the case without std unless the embedder sets one.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    let sleep = crate::time::sleep(duration);
+    Timeout { future, sleep }
+}
+
+/// A future that wraps another one with a deadline.
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety. The future is never moved out of the pinned self.
+        let self_ = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut self_.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(&mut self_.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err("timed out")),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
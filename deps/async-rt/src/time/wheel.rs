@@ -0,0 +1,157 @@
+use core::task::Waker;
+use core::time::Duration;
+
+use crate::prelude::*;
+
+/// A timer that fires at a deadline.
+pub(crate) struct TimerEntry {
+    deadline: Duration,
+    is_fired: AtomicBool,
+    is_cancelled: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+    // The count of pending timers that the timer is in, if any. The timer is
+    // taken off the count as soon as it is fired or cancelled.
+    counter: Mutex<Option<Arc<AtomicU64>>>,
+}
+
+impl TimerEntry {
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            is_fired: AtomicBool::new(false),
+            is_cancelled: AtomicBool::new(false),
+            waker: Mutex::new(None),
+            counter: Mutex::new(None),
+        }
+    }
+
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    pub fn is_fired(&self) -> bool {
+        self.is_fired.load(Ordering::Acquire)
+    }
+
+    /// Set the waker to be woken up when the timer fires.
+    pub fn set_waker(&self, waker: &Waker) {
+        let mut waker_slot = self.waker.lock();
+        match waker_slot.as_ref() {
+            Some(old_waker) if old_waker.will_wake(waker) => {}
+            _ => *waker_slot = Some(waker.clone()),
+        }
+    }
+
+    /// Cancel the timer so that the wheel can discard it.
+    ///
+    /// The timer no longer counts as pending from now on, even though it stays
+    /// in the wheel until the wheel advances to its slot.
+    pub fn cancel(&self) {
+        let mut counter = self.counter.lock();
+        self.is_cancelled.store(true, Ordering::Relaxed);
+        if let Some(counter) = counter.take() {
+            counter.fetch_sub(1, Ordering::AcqRel);
+        }
+        drop(counter);
+        self.waker.lock().take();
+    }
+
+    /// Count the timer as pending until it is fired or cancelled. Returns false
+    /// if it has already been cancelled.
+    pub fn count_in(&self, counter: &Arc<AtomicU64>) -> bool {
+        let mut slot = self.counter.lock();
+        if self.is_cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        counter.fetch_add(1, Ordering::AcqRel);
+        *slot = Some(counter.clone());
+        true
+    }
+
+    fn fire(&self) -> Option<Waker> {
+        self.is_fired.store(true, Ordering::Release);
+        if let Some(counter) = self.counter.lock().take() {
+            counter.fetch_sub(1, Ordering::AcqRel);
+        }
+        self.waker.lock().take()
+    }
+
+    fn deadline_tick(&self) -> u64 {
+        // Round up so that a timer never fires before its deadline
+        let tick_nanos = TICK.as_nanos();
+        ((self.deadline.as_nanos() + tick_nanos - 1) / tick_nanos) as u64
+    }
+}
+
+/// A hashed timing wheel.
+///
+/// A timer is put into the slot that its deadline tick hashes to. When the wheel
+/// advances by one tick, only the timers in the corresponding slot are checked.
+/// The timers that are more than one round of the wheel away simply stay in
+/// their slots until their rounds come.
+pub(crate) struct TimerWheel {
+    slots: Vec<Vec<Arc<TimerEntry>>>,
+    current_tick: u64,
+}
+
+/// The number of slots in a wheel.
+const NUM_SLOTS: usize = 512;
+
+/// The time span of a tick, which is the resolution of timers.
+pub(crate) const TICK: Duration = Duration::from_millis(1);
+
+impl TimerWheel {
+    pub fn new(now: Duration) -> Self {
+        let slots = (0..NUM_SLOTS).map(|_| Vec::new()).collect();
+        let current_tick = elapsed_ticks(now);
+        Self {
+            slots,
+            current_tick,
+        }
+    }
+
+    /// Add a timer to the wheel. Returns false if the timer has already expired.
+    pub fn insert(&mut self, entry: Arc<TimerEntry>) -> bool {
+        let deadline_tick = entry.deadline_tick();
+        if deadline_tick <= self.current_tick {
+            return false;
+        }
+
+        let slot_idx = (deadline_tick % NUM_SLOTS as u64) as usize;
+        self.slots[slot_idx].push(entry);
+        true
+    }
+
+    /// Advance the wheel to the given time and collect the wakers of the timers
+    /// that have expired.
+    pub fn advance(&mut self, now: Duration, wakers: &mut Vec<Waker>) {
+        let now_tick = elapsed_ticks(now);
+        if now_tick <= self.current_tick {
+            return;
+        }
+
+        // No need to visit a slot more than once
+        let num_ticks = (now_tick - self.current_tick).min(NUM_SLOTS as u64);
+        for tick in (self.current_tick + 1)..=(self.current_tick + num_ticks) {
+            let slot_idx = (tick % NUM_SLOTS as u64) as usize;
+            self.slots[slot_idx].retain(|entry| {
+                if entry.is_cancelled.load(Ordering::Relaxed) {
+                    return false;
+                }
+                if entry.deadline_tick() > now_tick {
+                    return true;
+                }
+                if let Some(waker) = entry.fire() {
+                    wakers.push(waker);
+                }
+                false
+            });
+        }
+        self.current_tick = now_tick;
+    }
+}
+
+/// Returns the number of ticks that have completely elapsed at a point of time.
+fn elapsed_ticks(now: Duration) -> u64 {
+    (now.as_nanos() / TICK.as_nanos()) as u64
+}
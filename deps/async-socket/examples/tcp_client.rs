@@ -5,8 +5,6 @@ use async_socket::{Socket, IoUringProvider};
 use io_uring_callback::{Builder, IoUring};
 use lazy_static::lazy_static;
 
-mod test_rt;
-
 lazy_static! {
     static ref RING: Arc<IoUring> = Arc::new(Builder::new().build(1024).unwrap());
 }
@@ -57,10 +55,11 @@ async fn tcp_client() {
 
 fn main() {
     let ring = RING.clone();
-    let actor = move || {
-        ring.trigger_callbacks();
-    };
-    test_rt::register_actor(actor);
-    
-    test_rt::run_blocking(tcp_client());
+    let actor = move || ring.trigger_callbacks() > 0;
+    let rt = async_rt::Builder::new()
+        .worker_threads(3)
+        .actor(actor)
+        .build()
+        .unwrap();
+    rt.block_on(tcp_client());
 }
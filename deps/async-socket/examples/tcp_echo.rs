@@ -1,12 +1,11 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 
+use async_rt::sync::Mutex;
 use async_socket::{Socket, IoUringProvider};
 use io_uring_callback::{Builder, IoUring};
 use lazy_static::lazy_static;
 
-mod test_rt;
-
 lazy_static! {
     static ref RING: Arc<IoUring> = Arc::new(Builder::new().build(1024).unwrap());
 }
@@ -42,12 +41,12 @@ async fn tcp_echo() {
     }
     println!("listen 127.0.0.1:3456");
 
-    let mut aff_id : Mutex<usize> = Mutex::new(0);
+    let aff_id = Mutex::new(0);
     loop {
         if let Ok(client) = socket.accept(None).await {
             println!("accept");
 
-            let mut guard = aff_id.lock().unwrap();
+            let mut guard = aff_id.lock().await;
             let aff_id = *guard;
             *guard += 1;
             drop(guard);
@@ -96,9 +95,11 @@ async fn tcp_echo() {
 
 fn main() {
     let ring = RING.clone();
-    let actor = move || {
-        ring.trigger_callbacks();
-    };
-    test_rt::register_actor(actor);
-    test_rt::run_blocking(tcp_echo());
+    let actor = move || ring.trigger_callbacks() > 0;
+    let rt = async_rt::Builder::new()
+        .worker_threads(3)
+        .actor(actor)
+        .build()
+        .unwrap();
+    rt.block_on(tcp_echo());
 }
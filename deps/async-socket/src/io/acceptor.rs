@@ -69,6 +69,9 @@ impl<P: IoUringProvider> Acceptor<P> {
     }
 
     pub async fn accept(self: &Arc<Self>, mut output_addr: Option<&mut libc::sockaddr_in>) -> i32 {
+        // Give other tasks a chance to run if this one has done too much work
+        async_rt::sched::consume_budget().await;
+
         // Init the poller only when needed
         let mut poller = None;
         loop {
@@ -61,6 +61,9 @@ impl<P: IoUringProvider> Receiver<P> {
     }
 
     pub async fn read(self: &Arc<Self>, buf: &mut [u8]) -> i32 {
+        // Give other tasks a chance to run if this one has done too much work
+        async_rt::sched::consume_budget().await;
+
         // Initialize the poller only when needed
         let mut poller = None;
         loop {
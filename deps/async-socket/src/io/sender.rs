@@ -49,6 +49,9 @@ impl<P: IoUringProvider> Sender<P> {
     }
 
     pub async fn write(self: &Arc<Self>, buf: &[u8]) -> i32 {
+        // Give other tasks a chance to run if this one has done too much work
+        async_rt::sched::consume_budget().await;
+
         // Initialize the poller only when needed
         let mut poller = None;
         loop {
@@ -24,6 +24,7 @@ pub use sgx_trts::libc;
 use std::prelude::v1::*;
 
 use std::io;
+use std::os::unix::io::RawFd;
 use std::sync::Arc;
 #[cfg(all(use_slab, not(sgx)))]
 use std::sync::Mutex;
@@ -318,31 +319,67 @@ impl IoUring {
         handle
     }
 
+    /// Have the eventfd signalled whenever a completion is posted, so that a
+    /// thread can sleep on it instead of polling for completions.
+    ///
+    /// Only one eventfd can be registered with an instance.
+    pub fn register_eventfd(&self, eventfd: RawFd) -> io::Result<()> {
+        self.inner.submitter().register_eventfd(eventfd)
+    }
+
     /// Scan for completed async I/O and trigger their registered callbacks.
-    #[cfg(not(use_slab))]
-    pub fn trigger_callbacks(&self) {
+    ///
+    /// Returns the number of callbacks that have been triggered.
+    pub fn trigger_callbacks(&self) -> usize {
         let cq = self.inner.completion();
+        let mut count = 0;
         while let Some(cqe) = cq.pop() {
-            let retval = cqe.result();
-            let token_idx = cqe.user_data() as usize;
-            let token = TOKEN_SLAB.get(token_idx).unwrap();
-            let callback = token.complete(retval);
-            (callback)(retval);
+            self.trigger_callback(cqe.user_data() as usize, cqe.result());
+            count += 1;
         }
-    }
-
-    #[cfg(use_slab)]
-    pub fn trigger_callbacks(&self) {
+        count
+    }
+
+    /// Like `trigger_callbacks`, but with the order of the callbacks decided by
+    /// `pick`, which is given the number of the remaining completions and returns
+    /// the index of the one to trigger next.
+    ///
+    /// This is intended for deterministic tests, where `pick` draws from a seeded
+    /// RNG to shuffle the completions that are ready at the same time.
+    ///
+    /// `pick` should return an index less than the number it is given. A larger
+    /// index is clamped to the last completion, since the completions have
+    /// already been taken from the completion queue and must not be lost.
+    pub fn trigger_callbacks_with(&self, mut pick: impl FnMut(usize) -> usize) -> usize {
         let cq = self.inner.completion();
+        let mut completions = Vec::new();
         while let Some(cqe) = cq.pop() {
-            let retval = cqe.result();
-            let token_idx = cqe.user_data() as usize;
-            let token_slab = TOKEN_SLAB.lock().unwrap();
-            let token = token_slab.get(token_idx).unwrap();
-            let callback = token.complete(retval);
-            drop(token_slab);
-            (callback)(retval);
+            completions.push((cqe.user_data() as usize, cqe.result()));
+        }
+
+        let count = completions.len();
+        while !completions.is_empty() {
+            let idx = pick(completions.len()).min(completions.len() - 1);
+            let (token_idx, retval) = completions.remove(idx);
+            self.trigger_callback(token_idx, retval);
         }
+        count
+    }
+
+    #[cfg(not(use_slab))]
+    fn trigger_callback(&self, token_idx: usize, retval: i32) {
+        let token = TOKEN_SLAB.get(token_idx).unwrap();
+        let callback = token.complete(retval);
+        (callback)(retval);
+    }
+
+    #[cfg(use_slab)]
+    fn trigger_callback(&self, token_idx: usize, retval: i32) {
+        let token_slab = TOKEN_SLAB.lock().unwrap();
+        let token = token_slab.get(token_idx).unwrap();
+        let callback = token.complete(retval);
+        drop(token_slab);
+        (callback)(retval);
     }
 
     /// Cancel all ongoing async I/O.
@@ -86,6 +86,12 @@ impl IoUring {
         }
     }
 
+    /// Get the submitter, e.g., to register an eventfd
+    #[inline]
+    pub fn submitter(&self) -> crate::Submitter<'_> {
+        self.ring.submitter()
+    }
+
     /// Get original IoUring instance
     pub fn into_inner(self) -> crate::IoUring {
         self.ring
@@ -34,6 +34,7 @@ extern crate lazy_static;
 
 use sgx_types::*;
 
+mod rt_support;
 mod tcp_echo_async_socket;
 mod tcp_echo_io_uring;
 mod tcp_echo_io_uring_callback;
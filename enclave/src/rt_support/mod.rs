@@ -0,0 +1,118 @@
+//! The platform support that async_rt needs inside the enclave, which is
+//! mostly implemented with OCalls since the enclave has no std of the host.
+
+use super::*;
+use sgx_trts::libc;
+use std::time::Duration;
+
+use async_rt::executor::{Parker, Unwinder};
+use async_rt::time::Clock;
+
+extern "C" {
+    fn ocall_eventfd_create(ret: *mut c_int) -> sgx_status_t;
+    fn ocall_eventfd_wait(
+        ret: *mut c_int,
+        fd: c_int,
+        completion_fd: c_int,
+        timeout_ns: c_long,
+    ) -> sgx_status_t;
+    fn ocall_eventfd_signal(ret: *mut c_int, fd: c_int) -> sgx_status_t;
+    fn ocall_clock_monotonic_ns(ret: *mut u64) -> sgx_status_t;
+}
+
+/// Create an eventfd of the host.
+pub fn create_eventfd() -> c_int {
+    let mut fd: c_int = -1;
+    let status = unsafe { ocall_eventfd_create(&mut fd) };
+    assert!(
+        status == sgx_status_t::SGX_SUCCESS && fd >= 0,
+        "failed to create an eventfd"
+    );
+    fd
+}
+
+/// A parker that sleeps on an eventfd of the host, so that an idle enclave
+/// thread gives up its CPU instead of spinning.
+pub struct EventfdParker {
+    fd: c_int,
+    // The eventfd that an io_uring signals upon completions, or -1 for none
+    completion_fd: c_int,
+}
+
+impl EventfdParker {
+    pub fn new() -> Self {
+        Self {
+            fd: create_eventfd(),
+            completion_fd: -1,
+        }
+    }
+
+    /// A parker that is also unparked by the completions of an io_uring, i.e.,
+    /// the eventfd has been registered with the ring (see
+    /// `IoUring::register_eventfd`). The eventfd is shared by the parkers and
+    /// outlives them.
+    ///
+    /// Without it, a parked thread would only see the completions when its
+    /// park times out.
+    pub fn with_completions(completion_fd: c_int) -> Self {
+        Self {
+            fd: create_eventfd(),
+            completion_fd,
+        }
+    }
+}
+
+impl Parker for EventfdParker {
+    fn park(&self, timeout: Option<Duration>) {
+        let timeout_ns = match timeout {
+            None => -1,
+            Some(timeout) => timeout.as_nanos().min(c_long::max_value() as u128) as c_long,
+        };
+        // A failed wait is no more than a spurious wakeup
+        let mut ret: c_int = 0;
+        unsafe { ocall_eventfd_wait(&mut ret, self.fd, self.completion_fd, timeout_ns) };
+    }
+
+    fn unpark(&self) {
+        let mut ret: c_int = 0;
+        unsafe { ocall_eventfd_signal(&mut ret, self.fd) };
+    }
+}
+
+impl Drop for EventfdParker {
+    fn drop(&mut self) {
+        unsafe { libc::ocall::close(self.fd) };
+    }
+}
+
+/// A clock that reads the monotonic clock of the host, since the enclave has
+/// no trusted time.
+///
+/// The host can lie about the time, which only affects when the timers fire.
+pub struct OcallClock;
+
+impl Clock for OcallClock {
+    fn now(&self) -> Duration {
+        let mut nanos: u64 = 0;
+        let status = unsafe { ocall_clock_monotonic_ns(&mut nanos) };
+        assert!(
+            status == sgx_status_t::SGX_SUCCESS,
+            "failed to read the clock"
+        );
+        Duration::from_nanos(nanos)
+    }
+}
+
+/// An unwinder with the panic support of `sgx_tstd`, so that a panicking task
+/// does not take down its enclave thread.
+pub struct SgxUnwinder;
+
+impl Unwinder for SgxUnwinder {
+    fn catch_unwind(&self, f: &mut dyn FnMut()) -> bool {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_ok()
+    }
+
+    fn panicking(&self) -> bool {
+        std::thread::panicking()
+    }
+}
@@ -1,16 +1,27 @@
 use super::*;
 use sgx_trts::libc;
 use std::prelude::v1::*;
-use std::sync::{Arc, SgxMutex as Mutex};
+use std::sync::Arc;
 
+use async_rt::sched::Affinity;
+use async_rt::sync::Mutex;
+use crate::rt_support::{create_eventfd, EventfdParker, OcallClock, SgxUnwinder};
 use async_socket::{Socket, IoUringProvider};
 use io_uring_callback::{Builder, IoUring};
 use lazy_static::lazy_static;
 
-mod test_rt;
+extern "C" {
+    fn ocall_sched_setaffinity(ret: *mut c_int, cpu: c_int) -> sgx_status_t;
+}
 
 lazy_static! {
     static ref RING: Arc<IoUring> = Arc::new(Builder::new().build(1024).unwrap());
+    // Signalled by the ring upon completions, which unparks the worker threads
+    static ref COMPLETION_FD: c_int = {
+        let fd = create_eventfd();
+        RING.register_eventfd(fd).unwrap();
+        fd
+    };
 }
 
 struct IoUringInstanceType {}
@@ -44,52 +55,45 @@ async fn tcp_echo() {
     }
     println!("listen 127.0.0.1:3456");
 
-    let mut aff_id : Mutex<usize> = Mutex::new(0);
+    let aff_id = Mutex::new(0);
     loop {
         if let Ok(client) = socket.accept(None).await {
             println!("accept");
 
-            let mut guard = aff_id.lock().unwrap();
+            let mut guard = aff_id.lock().await;
             let aff_id = *guard;
             *guard += 1;
             drop(guard);
 
-            async_rt::task::spawn(async move {
-                use async_rt::sched::Affinity;
-
-                let current = async_rt::task::current();
-
-                let mut affinity = current.sched_info().affinity().write();
-                assert!(affinity.is_full());
-
-                let new_affinity = {
-                    let mut new_affinity = Affinity::new_empty();
-                    new_affinity.set(aff_id, true);
-                    new_affinity
-                };
-                *affinity = new_affinity.clone();
-                drop(affinity);
-                println!("set affinity: {}", aff_id);
-
-                async_rt::sched::yield_().await;
-
-                assert!(*current.sched_info().affinity().read() == new_affinity);
-
-                let mut buf = vec![0u8; 2048];
-
-                loop {
-                    let bytes_read = client.read(buf.as_mut_slice()).await;
-
-                    if bytes_read == 0 {
-                        println!("shutdown");
-                        break;
+            // Spread the connections over the worker threads, one thread each
+            let parallelism = async_rt::Handle::current().parallelism() as usize;
+            let affinity = {
+                let mut affinity = Affinity::empty(parallelism);
+                affinity.set(aff_id % parallelism, true);
+                affinity
+            };
+            let spawn_res = async_rt::task::Builder::new()
+                .name(format!("echo-{}", aff_id))
+                .affinity(affinity)
+                .spawn(async move {
+                    let mut buf = vec![0u8; 2048];
+
+                    loop {
+                        let bytes_read = client.read(buf.as_mut_slice()).await;
+
+                        if bytes_read == 0 {
+                            println!("shutdown");
+                            break;
+                        }
+
+                        let bytes_write = client.write(buf.as_slice()).await;
+
+                        assert_eq!(bytes_read, bytes_write);
                     }
-
-                    let bytes_write = client.write(buf.as_slice()).await;
-
-                    assert_eq!(bytes_read, bytes_write);
-                }
-            });
+                });
+            if spawn_res.is_err() {
+                println!("spawn() return err.");
+            }
         } else {
             println!("accept() return err.");
         }
@@ -98,11 +102,36 @@ async fn tcp_echo() {
 
 pub fn tcp_echo_async_socket() -> sgx_status_t {
     let ring = RING.clone();
-    let actor = move || {
-        ring.trigger_callbacks();
-    };
-    test_rt::register_actor(actor);
-    test_rt::run_blocking(tcp_echo());
+    let actor = move || ring.trigger_callbacks() > 0;
+    // The clock is process-wide, so it may have been set by an earlier ECALL
+    let _ = async_rt::time::set_clock(OcallClock);
+    let rt = async_rt::Builder::new()
+        .worker_threads(3)
+        .actor(actor)
+        .parker(|| EventfdParker::with_completions(*COMPLETION_FD))
+        .worker_cpus(vec![0, 1, 2])
+        .cpu_pinner(pin_to_cpu)
+        .unwinder(SgxUnwinder)
+        // Timing every poll would make two OCalls to read the clock
+        .timing_metrics(false)
+        .thread_spawner(|_thread_name, worker| {
+            std::thread::spawn(worker);
+            Ok(())
+        })
+        .build()
+        .unwrap();
+    rt.block_on(tcp_echo());
 
     sgx_status_t::SGX_ERROR_UNEXPECTED
 }
+
+// Pin the current thread to the given CPU, which also pins the enclave thread
+// running on top of it.
+fn pin_to_cpu(cpu: u32) -> Result<(), &'static str> {
+    let mut ret: c_int = 0;
+    let status = unsafe { ocall_sched_setaffinity(&mut ret, cpu as c_int) };
+    if status != sgx_status_t::SGX_SUCCESS || ret != 0 {
+        return Err("failed to pin the thread to a CPU");
+    }
+    Ok(())
+}